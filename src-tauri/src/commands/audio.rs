@@ -0,0 +1,203 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tauri::ipc::Response;
+use tauri::State;
+
+use super::blocking;
+use crate::errors::AppResult;
+use crate::services::analyzer::chroma::{self, ChromaOptions, Chromagram};
+use crate::services::analyzer::features::{self, AudioFeatures, FeatureOptions};
+use crate::services::analyzer::loudness::{self, LoudnessAnalysis};
+use crate::services::analyzer::peaks::{self, PeaksRequest, WaveformPeaks};
+use crate::services::analyzer::pitch::{self, PitchOptions, PitchTrack};
+use crate::services::analyzer::rhythm::{self, RhythmAnalysis, RhythmOptions};
+use crate::services::analyzer::spectrogram::{self, SpectrogramOptions};
+use crate::services::analyzer::spectrum::{self, SpectrumFrames, SpectrumOptions};
+use crate::services::audio_store::AudioStore;
+use crate::services::decoder;
+use crate::services::figure::{self, FigureOptions};
+use crate::services::peak_cache::PeakCache;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedAudioInfo {
+    pub handle: u32,
+    pub sample_rate: u32,
+    pub channels: usize,
+    pub frames: usize,
+    pub duration: f64,
+}
+
+/// Decode an audio file natively and keep the PCM around under a handle.
+#[tauri::command]
+pub async fn decode_audio(
+    path: String,
+    store: State<'_, AudioStore>,
+) -> AppResult<DecodedAudioInfo> {
+    let path = PathBuf::from(path);
+    let decoded = blocking(move || decoder::decode_file(&path)).await?;
+
+    let info = DecodedAudioInfo {
+        handle: 0,
+        sample_rate: decoded.sample_rate,
+        channels: decoded.channels.len(),
+        frames: decoded.frames(),
+        duration: decoded.duration(),
+    };
+    let handle = store.insert(decoded);
+    Ok(DecodedAudioInfo { handle, ..info })
+}
+
+/// Planar little-endian f32 PCM for a handle, returned as a raw ArrayBuffer.
+#[tauri::command]
+pub fn read_decoded_audio(handle: u32, store: State<'_, AudioStore>) -> AppResult<Response> {
+    let audio = store.get(handle)?;
+    let mut bytes = Vec::with_capacity(audio.frames() * audio.channels.len() * 4);
+    for channel in &audio.channels {
+        for sample in channel {
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+    }
+    Ok(Response::new(bytes))
+}
+
+#[tauri::command]
+pub fn release_decoded_audio(
+    handle: u32,
+    store: State<'_, AudioStore>,
+    peaks: State<'_, PeakCache>,
+) -> bool {
+    peaks.remove(handle);
+    store.remove(handle)
+}
+
+/// Offline magnitude spectra for a decoded file, in the `AnalyserNode` byte scale.
+#[tauri::command]
+pub async fn analyze_spectrum(
+    handle: u32,
+    options: SpectrumOptions,
+    store: State<'_, AudioStore>,
+) -> AppResult<SpectrumFrames> {
+    let audio = store.get(handle)?;
+    blocking(move || spectrum::analyze(&audio.mixdown(), audio.sample_rate, &options)).await
+}
+
+/// RMS, spectral shape, zero-crossing rate and band energies for a decoded
+/// file, one value per video frame when `frameRate` is given.
+#[tauri::command]
+pub async fn extract_features(
+    handle: u32,
+    options: FeatureOptions,
+    store: State<'_, AudioStore>,
+) -> AppResult<AudioFeatures> {
+    let audio = store.get(handle)?;
+    blocking(move || features::extract(&audio.mixdown(), audio.sample_rate, &options)).await
+}
+
+/// Full-track STFT of a decoded file on a linear, log or mel frequency axis,
+/// normalised in dB against its loudest cell. Returned as a raw ArrayBuffer:
+/// a JSON header followed by the values (see `Spectrogram::to_bytes`).
+#[tauri::command]
+pub async fn compute_spectrogram(
+    handle: u32,
+    options: SpectrogramOptions,
+    store: State<'_, AudioStore>,
+) -> AppResult<Response> {
+    let audio = store.get(handle)?;
+    let bytes = blocking(move || {
+        spectrogram::compute(&audio.mixdown(), audio.sample_rate, &options)?.to_bytes()
+    })
+    .await?;
+    Ok(Response::new(bytes))
+}
+
+/// Render a full-track spectrogram at `figure.width` x `figure.height` with
+/// frequency and time axes, and write it to `path` as PNG, TIFF or EXR.
+#[tauri::command]
+pub async fn export_spectrogram_image(
+    handle: u32,
+    path: String,
+    options: SpectrogramOptions,
+    figure: FigureOptions,
+    store: State<'_, AudioStore>,
+) -> AppResult<String> {
+    let audio = store.get(handle)?;
+    figure::image_format(Path::new(&path), figure.width, figure.height)?;
+    blocking(move || {
+        let spectrogram = spectrogram::compute(&audio.mixdown(), audio.sample_rate, &options)?;
+        let image = figure::render(&spectrogram, options.scale, &figure)?;
+        figure::save(&image, Path::new(&path))?;
+        Ok(path)
+    })
+    .await
+}
+
+/// EBU R128 loudness of a decoded file: integrated LUFS, loudness range, true
+/// peak and the momentary/short-term curves.
+#[tauri::command]
+pub async fn analyze_loudness(
+    handle: u32,
+    store: State<'_, AudioStore>,
+) -> AppResult<LoudnessAnalysis> {
+    let audio = store.get(handle)?;
+    blocking(move || loudness::measure(&audio.channels, audio.sample_rate)).await
+}
+
+/// Chromagram of a decoded file with the estimated key of the whole file and
+/// of each segment.
+#[tauri::command]
+pub async fn analyze_chroma(
+    handle: u32,
+    options: ChromaOptions,
+    store: State<'_, AudioStore>,
+) -> AppResult<Chromagram> {
+    let audio = store.get(handle)?;
+    blocking(move || chroma::analyze(&audio.mixdown(), audio.sample_rate, &options)).await
+}
+
+/// f0 curve and voicing confidence of a decoded (monophonic) file.
+#[tauri::command]
+pub async fn track_pitch(
+    handle: u32,
+    options: PitchOptions,
+    store: State<'_, AudioStore>,
+) -> AppResult<PitchTrack> {
+    let audio = store.get(handle)?;
+    blocking(move || pitch::analyze(&audio.mixdown(), audio.sample_rate, &options)).await
+}
+
+/// Onsets, beats, downbeats, global BPM and a tempo curve for a decoded file.
+#[tauri::command]
+pub async fn analyze_rhythm(
+    handle: u32,
+    options: RhythmOptions,
+    store: State<'_, AudioStore>,
+) -> AppResult<RhythmAnalysis> {
+    let audio = store.get(handle)?;
+    blocking(move || rhythm::analyze(&audio.mixdown(), audio.sample_rate, &options)).await
+}
+
+/// Min/max/RMS per bucket over any time range of a decoded file, for
+/// full-track overviews and zoomable timelines. The first call per
+/// handle/channel builds a peak pyramid that later calls reuse.
+#[tauri::command]
+pub async fn compute_waveform_peaks(
+    handle: u32,
+    request: PeaksRequest,
+    store: State<'_, AudioStore>,
+    cache: State<'_, PeakCache>,
+) -> AppResult<WaveformPeaks> {
+    let audio = store.get(handle)?;
+    let cache = cache.inner().clone();
+    blocking(move || {
+        let source = cache.get_or_build(handle, request.channel, &audio)?;
+        peaks::compute(
+            &source.pyramid,
+            &source.samples,
+            audio.sample_rate,
+            &request,
+        )
+    })
+    .await
+}
@@ -0,0 +1,11 @@
+use tauri::ipc::Response;
+
+use crate::errors::AppResult;
+use crate::services::colormap::{self, ColormapSpec};
+
+/// 256-entry RGB lookup table (768 bytes) for a named map or custom gradient,
+/// indexed by the 0–255 spectrogram intensity.
+#[tauri::command]
+pub fn get_colormap_lut(colormap: ColormapSpec) -> AppResult<Response> {
+    Ok(Response::new(colormap::lut(&colormap)?.to_bytes()))
+}
@@ -0,0 +1,173 @@
+use std::path::{Path, PathBuf};
+
+use tauri::ipc::{InvokeBody, Request};
+use tauri::{AppHandle, Emitter, State};
+
+use super::blocking;
+use crate::errors::{AppError, AppResult};
+use crate::services::encoder::{RenderJob, RenderRequest};
+use crate::services::ffmpeg;
+use crate::services::ffmpeg::jobs::ConversionJobs;
+use crate::services::ffmpeg::normalize::LoudnessTarget;
+use crate::services::ffmpeg::output::{resolve_output_path, ExistingFilePolicy};
+use crate::services::ffmpeg::probe::FfmpegReport;
+use crate::services::ffmpeg::profile::{resolve_profile, ProfileSpec};
+use crate::services::render_store::RenderStore;
+use crate::services::settings::SettingsStore;
+
+/// Header carrying the job id on `push_frame`, whose body is the raw RGBA frame.
+const RENDER_JOB_HEADER: &str = "x-render-job";
+
+/// Event carrying `ConversionProgress` while `convert_video` runs, and while
+/// `start_render` measures loudness.
+const CONVERSION_PROGRESS_EVENT: &str = "conversion-progress";
+
+/// Convert a video with FFmpeg, emitting `conversion-progress` events.
+///
+/// The output goes to `output_path`, or next to the input with the extension
+/// replaced by `output_format`. An existing file is handled per `on_existing`
+/// (default: pick a unique name); the input itself is never overwritten.
+/// `profile` is a quality preset name or a full `ExportProfile`; it defaults to
+/// the profile in the app settings, then `medium`.
+/// With `loudness`, the audio track is normalized to that target (measured
+/// in Rust, or by a first `loudnorm` pass in FFmpeg).
+/// Pass `job_id` to be able to stop it with `cancel_conversion`.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn convert_video(
+    app: AppHandle,
+    input_path: String,
+    output_format: String,
+    output_path: Option<String>,
+    on_existing: Option<ExistingFilePolicy>,
+    profile: Option<ProfileSpec>,
+    loudness: Option<LoudnessTarget>,
+    job_id: Option<String>,
+    jobs: State<'_, ConversionJobs>,
+    settings: State<'_, SettingsStore>,
+) -> AppResult<String> {
+    let input_path = PathBuf::from(input_path);
+    let output_path = resolve_output_path(
+        &input_path,
+        output_path.as_deref().map(Path::new),
+        &output_format,
+        on_existing.unwrap_or_default(),
+    )?;
+    let profile = resolve_profile(
+        profile.or_else(|| settings.default_export_profile()),
+        &output_path,
+    )?;
+    let ffmpeg_path = settings.ffmpeg_path();
+
+    let jobs = jobs.inner().clone();
+    let job_id = job_id.unwrap_or_else(|| jobs.generate_id());
+    blocking(move || {
+        ffmpeg::convert(
+            ffmpeg_path.as_deref(),
+            &input_path,
+            &output_path,
+            &profile,
+            loudness.as_ref(),
+            &jobs,
+            &job_id,
+            |progress| {
+                let _ = app.emit(CONVERSION_PROGRESS_EVENT, progress);
+            },
+        )?;
+        Ok(output_path.to_string_lossy().into_owned())
+    })
+    .await
+}
+
+/// Kill a running `convert_video` job, or the loudness measurement of a
+/// `start_render`. Returns false if it already finished.
+#[tauri::command]
+pub fn cancel_conversion(job_id: String, jobs: State<'_, ConversionJobs>) -> bool {
+    jobs.cancel(&job_id)
+}
+
+/// Find FFmpeg (user-configured path, bundled sidecar, then `PATH`) and report
+/// its version, encoders, muxers, hardware accelerators and producible formats.
+#[tauri::command]
+pub async fn check_ffmpeg_installed(settings: State<'_, SettingsStore>) -> AppResult<FfmpegReport> {
+    let ffmpeg_path = settings.ffmpeg_path();
+    blocking(move || ffmpeg::report(ffmpeg_path.as_deref())).await
+}
+
+/// Start an FFmpeg process that encodes pushed frames at the exact frame rate.
+///
+/// With `request.loudness`, the audio is measured first; that step emits
+/// `conversion-progress` events in the `measuring` phase under `job_id`, and
+/// `cancel_conversion` stops it. Once started, the render is stopped with
+/// `cancel_render`.
+#[tauri::command]
+pub async fn start_render(
+    app: AppHandle,
+    request: RenderRequest,
+    job_id: Option<String>,
+    store: State<'_, RenderStore>,
+    jobs: State<'_, ConversionJobs>,
+    settings: State<'_, SettingsStore>,
+) -> AppResult<u32> {
+    let ffmpeg_path = settings.ffmpeg_path();
+    let default_profile = settings.default_export_profile();
+    let jobs = jobs.inner().clone();
+    let job_id = job_id.unwrap_or_else(|| jobs.generate_id());
+    let job = blocking(move || {
+        let handle = jobs.register(&job_id)?;
+        let job = RenderJob::spawn(
+            &request,
+            ffmpeg_path.as_deref(),
+            default_profile,
+            &handle,
+            &job_id,
+            |progress| {
+                let _ = app.emit(CONVERSION_PROGRESS_EVENT, progress);
+            },
+        );
+        jobs.remove(&job_id);
+        job
+    })
+    .await?;
+    Ok(store.insert(job))
+}
+
+/// Append one RGBA frame (`width * height * 4` bytes) to a render job.
+#[tauri::command]
+pub async fn push_frame(request: Request<'_>, store: State<'_, RenderStore>) -> AppResult<u64> {
+    let InvokeBody::Raw(frame) = request.body() else {
+        return Err(AppError::InvalidArgument(
+            "push_frame expects a raw RGBA body".to_string(),
+        ));
+    };
+    let job_id = request
+        .headers()
+        .get(RENDER_JOB_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u32>().ok())
+        .ok_or_else(|| {
+            AppError::InvalidArgument(format!("Missing or invalid {} header", RENDER_JOB_HEADER))
+        })?;
+
+    let job = store.get(job_id)?;
+    let frame = frame.clone();
+    blocking(move || job.write_frame(&frame)).await
+}
+
+/// Close the frame stream and return the path of the finished video.
+#[tauri::command]
+pub async fn finish_render(job_id: u32, store: State<'_, RenderStore>) -> AppResult<String> {
+    let job = store.take(job_id)?;
+    blocking(move || job.finish()).await
+}
+
+/// Abort a render job and remove its partial output.
+#[tauri::command]
+pub async fn cancel_render(job_id: u32, store: State<'_, RenderStore>) -> AppResult<()> {
+    let job = store.take(job_id)?;
+    blocking(move || {
+        job.cancel();
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,134 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use serde::Serialize;
+use tauri::State;
+
+use super::audio::DecodedAudioInfo;
+use super::blocking;
+use crate::errors::AppResult;
+use crate::services::audio_store::AudioStore;
+use crate::services::midi::{self, IndexedMidi, MidiFile, MidiWindow};
+use crate::services::midi_store::MidiStore;
+use crate::services::synth::{self, SynthOptions};
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadedMidiInfo {
+    pub handle: u32,
+    pub duration: f64,
+    pub tracks: usize,
+    pub notes: usize,
+    /// `None` when the file has no notes.
+    pub lowest_pitch: Option<u8>,
+    pub highest_pitch: Option<u8>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SynthesizedMidiInfo {
+    /// WAV file the rendered audio was written to.
+    pub path: String,
+    #[serde(flatten)]
+    pub audio: DecodedAudioInfo,
+}
+
+/// Parse a Standard MIDI File (type 0/1) into tracks, notes, the full tempo
+/// map, time/key signatures, program changes and controller events.
+#[tauri::command]
+pub async fn parse_midi(path: String) -> AppResult<MidiFile> {
+    let path = PathBuf::from(path);
+    blocking(move || midi::parse_file(&path)).await
+}
+
+/// Parse a MIDI file and keep it around under a handle for window queries.
+#[tauri::command]
+pub async fn load_midi(path: String, store: State<'_, MidiStore>) -> AppResult<LoadedMidiInfo> {
+    let path = PathBuf::from(path);
+    let indexed = blocking(move || midi::parse_file(&path).map(IndexedMidi::new)).await?;
+
+    let range = indexed.pitch_range();
+    let info = LoadedMidiInfo {
+        handle: 0,
+        duration: indexed.file.duration,
+        tracks: indexed.file.tracks.len(),
+        notes: indexed.note_count(),
+        lowest_pitch: range.map(|(lowest, _)| lowest),
+        highest_pitch: range.map(|(_, highest)| highest),
+    };
+    let handle = store.insert(indexed);
+    Ok(LoadedMidiInfo { handle, ..info })
+}
+
+/// Notes of every track sounding between `start` and `end` seconds, plus the
+/// bars overlapping that window.
+#[tauri::command]
+pub fn midi_notes_in_window(
+    handle: u32,
+    start: f64,
+    end: f64,
+    store: State<'_, MidiStore>,
+) -> AppResult<MidiWindow> {
+    store.get(handle)?.window(start, end)
+}
+
+#[tauri::command]
+pub fn release_midi(handle: u32, store: State<'_, MidiStore>) -> bool {
+    store.remove(handle)
+}
+
+/// Numbers the temporary WAV files of `synthesize_midi` within this run.
+static NEXT_RENDER: AtomicU32 = AtomicU32::new(0);
+
+/// Render a MIDI file through an SF2 SoundFont, write the result as a WAV file
+/// and keep the PCM around under an audio handle, like `decode_audio`. Without
+/// `output_path` the WAV is a temporary file, deleted once the handle is
+/// released.
+#[tauri::command]
+pub async fn synthesize_midi(
+    path: String,
+    sound_font: String,
+    output_path: Option<String>,
+    options: SynthOptions,
+    store: State<'_, AudioStore>,
+) -> AppResult<SynthesizedMidiInfo> {
+    let path = PathBuf::from(path);
+    let temporary = output_path.is_none();
+    let output = match output_path {
+        Some(output) => PathBuf::from(output),
+        None => {
+            let stem = path
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            std::env::temp_dir().join(format!(
+                "music-visualizer-{}-{}-{}.wav",
+                stem,
+                std::process::id(),
+                NEXT_RENDER.fetch_add(1, Ordering::Relaxed)
+            ))
+        }
+    };
+    let rendered = blocking(move || {
+        let file = midi::parse_file(&path)?;
+        let mut audio = synth::render(&file, &PathBuf::from(sound_font), output, &options)?;
+        audio.temporary = temporary;
+        synth::write_wav(&audio)?;
+        Ok(audio)
+    })
+    .await?;
+
+    let path = rendered.path.to_string_lossy().into_owned();
+    let info = DecodedAudioInfo {
+        handle: 0,
+        sample_rate: rendered.sample_rate,
+        channels: rendered.channels.len(),
+        frames: rendered.frames(),
+        duration: rendered.duration(),
+    };
+    let handle = store.insert(rendered);
+    Ok(SynthesizedMidiInfo {
+        path,
+        audio: DecodedAudioInfo { handle, ..info },
+    })
+}
@@ -0,0 +1,19 @@
+pub mod audio;
+pub mod colormap;
+pub mod export;
+pub mod midi;
+pub mod project;
+pub mod settings;
+
+use crate::errors::{AppError, AppResult};
+
+/// Run blocking work (decoding, analysis, FFmpeg) off the async runtime.
+pub(crate) async fn blocking<T, F>(task: F) -> AppResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> AppResult<T> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(task)
+        .await
+        .map_err(|e| AppError::Internal(format!("Background task failed: {}", e)))?
+}
@@ -0,0 +1,21 @@
+use std::path::PathBuf;
+
+use crate::domain::project::{ProjectState, PROJECT_EXTENSION};
+use crate::errors::AppResult;
+use crate::services::storage;
+
+/// Save the composer state as a versioned `.mvproj` JSON file.
+#[tauri::command]
+pub async fn save_project(path: String, project: ProjectState) -> AppResult<String> {
+    let mut path = PathBuf::from(path);
+    if path.extension().is_none() {
+        path.set_extension(PROJECT_EXTENSION);
+    }
+    storage::write_project(&path, &project)?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+#[tauri::command]
+pub async fn load_project(path: String) -> AppResult<ProjectState> {
+    storage::read_project(&PathBuf::from(path))
+}
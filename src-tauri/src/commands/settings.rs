@@ -0,0 +1,19 @@
+use tauri::State;
+
+use crate::errors::AppResult;
+use crate::services::settings::{AppSettings, SettingsStore};
+
+#[tauri::command]
+pub fn get_settings(store: State<'_, SettingsStore>) -> AppSettings {
+    store.get()
+}
+
+/// Replace the app settings and save them to the app config dir.
+/// Returns the settings as stored (blank paths cleared).
+#[tauri::command]
+pub fn update_settings(
+    settings: AppSettings,
+    store: State<'_, SettingsStore>,
+) -> AppResult<AppSettings> {
+    store.update(settings)
+}
@@ -0,0 +1,191 @@
+use serde_json::{Map, Value};
+
+use super::project::PROJECT_VERSION;
+use crate::errors::{AppError, AppResult};
+
+/// Upgrades a project document by exactly one schema version.
+type Migration = fn(&mut Map<String, Value>) -> Result<(), String>;
+
+/// `MIGRATIONS[i]` upgrades version `i + 1` to `i + 2`.
+const MIGRATIONS: &[Migration] = &[v1_to_v2];
+
+const _: () = assert!(MIGRATIONS.len() as u32 + 1 == PROJECT_VERSION);
+
+/// Upgrade a raw project document in place to `PROJECT_VERSION`, one step at a
+/// time. Returns the version the document was written with.
+pub fn migrate(document: &mut Value) -> AppResult<u32> {
+    let object = document
+        .as_object_mut()
+        .ok_or_else(|| AppError::InvalidProject("not a JSON object".to_string()))?;
+    let version = object
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| AppError::InvalidProject("missing version".to_string()))?
+        as u32;
+
+    if version == 0 || version > PROJECT_VERSION {
+        return Err(AppError::InvalidProject(format!(
+            "unsupported version {} (this build reads up to version {})",
+            version, PROJECT_VERSION
+        )));
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+        migration(object).map_err(|e| {
+            AppError::InvalidProject(format!("upgrade from version {} failed: {}", index + 1, e))
+        })?;
+        object.insert("version".to_string(), Value::from(index as u32 + 2));
+    }
+    Ok(version)
+}
+
+fn layers_mut(document: &mut Map<String, Value>) -> Result<&mut Vec<Value>, String> {
+    document
+        .get_mut("layers")
+        .and_then(Value::as_array_mut)
+        .ok_or_else(|| "missing layers".to_string())
+}
+
+/// v2: waveform layers replace `strokeOpacity` with `waveformTransparency`
+/// (0 = opaque, 1 = invisible), and every layer gets a `settings` object.
+fn v1_to_v2(document: &mut Map<String, Value>) -> Result<(), String> {
+    for layer in layers_mut(document)? {
+        let layer = layer
+            .as_object_mut()
+            .ok_or_else(|| "layer is not an object".to_string())?;
+        let is_waveform = layer.get("type").and_then(Value::as_str) == Some("waveform");
+
+        let settings = layer
+            .entry("settings")
+            .or_insert_with(|| Value::Object(Map::new()));
+        if !settings.is_object() {
+            *settings = Value::Object(Map::new());
+        }
+        let settings = settings
+            .as_object_mut()
+            .expect("settings was just made an object");
+
+        if is_waveform {
+            let legacy_opacity = settings.remove("strokeOpacity").and_then(|v| v.as_f64());
+            if !settings.contains_key("waveformTransparency") {
+                let transparency =
+                    legacy_opacity.map_or(0.0, |opacity| (1.0 - opacity).clamp(0.0, 1.0));
+                settings.insert(
+                    "waveformTransparency".to_string(),
+                    Value::from(transparency),
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn v1_document(layers: Value) -> Value {
+        json!({
+            "version": 1,
+            "globalSettings": {
+                "aspectRatio": "16:9",
+                "resolution": "1920x1080",
+                "backgroundColor": "#000000",
+                "exportFormat": "mp4",
+                "frameRate": "30",
+                "quality": "high"
+            },
+            "layers": layers,
+            "files": []
+        })
+    }
+
+    fn waveform_layer(settings: Value) -> Value {
+        json!({
+            "id": "layer-1",
+            "type": "waveform",
+            "name": "Waveform",
+            "visible": true,
+            "opacity": 1.0,
+            "x": 0.0,
+            "y": 0.0,
+            "width": 50.0,
+            "height": 20.0,
+            "settings": settings
+        })
+    }
+
+    #[test]
+    fn v1_to_v2_converts_stroke_opacity() {
+        let mut document = v1_document(json!([waveform_layer(
+            json!({ "color": "#ffffff", "strokeOpacity": 0.25 })
+        )]));
+        assert_eq!(migrate(&mut document), Ok(1));
+
+        let settings = &document["layers"][0]["settings"];
+        assert_eq!(document["version"], json!(2));
+        assert_eq!(settings["waveformTransparency"], json!(0.75));
+        assert!(settings.get("strokeOpacity").is_none());
+        assert_eq!(settings["color"], json!("#ffffff"));
+    }
+
+    #[test]
+    fn v1_to_v2_defaults_missing_transparency_and_keeps_existing() {
+        let mut document = v1_document(json!([
+            waveform_layer(json!({})),
+            waveform_layer(json!({ "waveformTransparency": 0.5, "strokeOpacity": 1.0 }))
+        ]));
+        migrate(&mut document).unwrap();
+
+        assert_eq!(
+            document["layers"][0]["settings"]["waveformTransparency"],
+            json!(0.0)
+        );
+        assert_eq!(
+            document["layers"][1]["settings"]["waveformTransparency"],
+            json!(0.5)
+        );
+    }
+
+    #[test]
+    fn v1_to_v2_adds_missing_settings_object() {
+        let mut layer = waveform_layer(json!(null));
+        layer["type"] = json!("spectrum");
+        let mut document = v1_document(json!([layer]));
+        migrate(&mut document).unwrap();
+
+        assert_eq!(document["layers"][0]["settings"], json!({}));
+    }
+
+    #[test]
+    fn migrated_v1_document_deserializes() {
+        let mut document = v1_document(json!([waveform_layer(json!({ "strokeOpacity": 0.0 }))]));
+        migrate(&mut document).unwrap();
+
+        let project: crate::domain::project::ProjectState =
+            serde_json::from_value(document).unwrap();
+        assert_eq!(project.version, PROJECT_VERSION);
+    }
+
+    #[test]
+    fn current_version_is_left_untouched() {
+        let mut document = v1_document(json!([waveform_layer(json!({ "strokeOpacity": 0.0 }))]));
+        document["version"] = json!(PROJECT_VERSION);
+        let before = document.clone();
+
+        assert_eq!(migrate(&mut document), Ok(PROJECT_VERSION));
+        assert_eq!(document, before);
+    }
+
+    #[test]
+    fn rejects_newer_and_missing_versions() {
+        let mut newer = v1_document(json!([]));
+        newer["version"] = json!(PROJECT_VERSION + 1);
+        assert!(migrate(&mut newer).is_err());
+
+        let mut unversioned = v1_document(json!([]));
+        unversioned.as_object_mut().unwrap().remove("version");
+        assert!(migrate(&mut unversioned).is_err());
+    }
+}
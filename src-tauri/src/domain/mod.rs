@@ -0,0 +1,3 @@
+pub mod migration;
+pub mod project;
+pub mod timeline;
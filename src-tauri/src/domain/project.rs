@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+/// Schema version written into every `.mvproj` file.
+pub const PROJECT_VERSION: u32 = 2;
+
+/// Extension used for saved compositions.
+pub const PROJECT_EXTENSION: &str = "mvproj";
+
+/// Composer state persisted by `save_project` / `load_project`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectState {
+    pub version: u32,
+    pub global_settings: GlobalSettings,
+    pub layers: Vec<LayerConfig>,
+    pub files: Vec<ProjectFile>,
+}
+
+/// Mirrors `globalSettings` in the composer.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GlobalSettings {
+    pub aspect_ratio: String,
+    pub resolution: String,
+    pub background_color: String,
+    pub export_format: String,
+    pub frame_rate: String,
+    pub quality: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayerType {
+    Spectrum,
+    Waveform,
+    Spectrogram,
+    #[serde(rename = "3d")]
+    ThreeD,
+    Chroma,
+    Pitch,
+    Pianoroll,
+    Score,
+}
+
+/// One composer `Layer`. `settings` stays free-form because each layer type
+/// owns its own keys (see `getDefaultSettings`).
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LayerConfig {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub layer_type: LayerType,
+    pub name: String,
+    pub visible: bool,
+    pub opacity: f64,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub settings: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assigned_file_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modulations: Vec<Modulation>,
+}
+
+/// Drives a numeric layer property from an `extract_features` curve.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Modulation {
+    /// `"opacity"` or a key of the layer's `settings`.
+    pub target: String,
+    /// Name of the curve, e.g. `"rms"` or `"centroid"`.
+    pub feature: String,
+    /// Values the property takes at the quietest and loudest frame of the curve.
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    Audio,
+    Midi,
+}
+
+/// A `LoadedFile` saved by reference: only its path is stored, not the data.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectFile {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: FileKind,
+    pub path: String,
+}
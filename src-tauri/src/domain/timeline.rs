@@ -0,0 +1,488 @@
+use serde::Serialize;
+
+use crate::errors::{AppError, AppResult};
+
+/// Tempo used until the first tempo event (120 BPM), per the SMF spec.
+pub const DEFAULT_MICROSECONDS_PER_QUARTER: u32 = 500_000;
+
+/// How a MIDI file's delta times map onto real time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TickBase {
+    /// Ticks per quarter note; seconds depend on the tempo map.
+    Metrical(u16),
+    /// Fixed ticks per second (SMPTE timecode), tempo events have no effect.
+    Timecode(f64),
+}
+
+impl TickBase {
+    /// Ticks per quarter note. Timecode files have no musical grid, so bars
+    /// are laid out as if at the default 120 BPM.
+    pub fn ticks_per_quarter(self) -> f64 {
+        match self {
+            TickBase::Metrical(ppq) => ppq as f64,
+            TickBase::Timecode(ticks_per_second) => {
+                ticks_per_second * DEFAULT_MICROSECONDS_PER_QUARTER as f64 / 1_000_000.0
+            }
+        }
+    }
+}
+
+/// A tempo that takes effect at `tick` and lasts until the next segment.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TempoSegment {
+    pub tick: u64,
+    /// Time at `tick`, accumulated over all earlier segments.
+    pub seconds: f64,
+    pub microseconds_per_quarter: u32,
+}
+
+/// Piecewise-constant tempo over ticks, for converting between ticks and seconds.
+#[derive(Clone, Debug)]
+pub struct TempoMap {
+    base: TickBase,
+    segments: Vec<TempoSegment>,
+}
+
+impl TempoMap {
+    /// Build from `(tick, microseconds per quarter)` changes in any order.
+    /// Later changes at the same tick win, and 120 BPM applies before the first.
+    pub fn new(base: TickBase, changes: &[(u64, u32)]) -> Self {
+        let mut sorted: Vec<(u64, u32)> = changes
+            .iter()
+            .copied()
+            .filter(|(_, tempo)| *tempo > 0)
+            .collect();
+        sorted.sort_by_key(|(tick, _)| *tick);
+
+        let mut map = TempoMap {
+            base,
+            segments: vec![TempoSegment {
+                tick: 0,
+                seconds: 0.0,
+                microseconds_per_quarter: DEFAULT_MICROSECONDS_PER_QUARTER,
+            }],
+        };
+        for (tick, tempo) in sorted {
+            let last = *map.segments.last().expect("tempo map has a first segment");
+            if tick == last.tick {
+                map.segments
+                    .last_mut()
+                    .expect("tempo map has a first segment")
+                    .microseconds_per_quarter = tempo;
+                continue;
+            }
+            let seconds = last.seconds + (tick - last.tick) as f64 * map.seconds_per_tick(&last);
+            map.segments.push(TempoSegment {
+                tick,
+                seconds,
+                microseconds_per_quarter: tempo,
+            });
+        }
+        map
+    }
+
+    fn seconds_per_tick(&self, segment: &TempoSegment) -> f64 {
+        match self.base {
+            TickBase::Metrical(ppq) => {
+                segment.microseconds_per_quarter as f64 / 1_000_000.0 / ppq.max(1) as f64
+            }
+            TickBase::Timecode(ticks_per_second) => 1.0 / ticks_per_second,
+        }
+    }
+
+    pub fn base(&self) -> TickBase {
+        self.base
+    }
+
+    /// Every tempo segment, starting at tick 0.
+    pub fn segments(&self) -> &[TempoSegment] {
+        &self.segments
+    }
+
+    /// Seconds from the start of the file to `tick`.
+    pub fn seconds_at(&self, tick: u64) -> f64 {
+        self.seconds_at_fractional(tick as f64)
+    }
+
+    /// `seconds_at` for positions between ticks (e.g. from a beat fraction).
+    pub fn seconds_at_fractional(&self, tick: f64) -> f64 {
+        let index = self
+            .segments
+            .partition_point(|segment| segment.tick as f64 <= tick);
+        let segment = &self.segments[index.saturating_sub(1)];
+        segment.seconds + (tick - segment.tick as f64) * self.seconds_per_tick(segment)
+    }
+
+    /// Fractional tick at `seconds`; the inverse of `seconds_at_fractional`.
+    pub fn tick_at(&self, seconds: f64) -> f64 {
+        let index = self
+            .segments
+            .partition_point(|segment| segment.seconds <= seconds);
+        let segment = &self.segments[index.saturating_sub(1)];
+        segment.tick as f64 + (seconds - segment.seconds) / self.seconds_per_tick(segment)
+    }
+}
+
+/// A time signature that takes effect at `tick` and starts a new bar there.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeterSegment {
+    pub tick: u64,
+    /// Zero-based index of the bar starting at `tick`.
+    pub bar: u32,
+    pub numerator: u8,
+    pub denominator: u32,
+}
+
+/// A bar/beat position: both one-based, the beat carrying a fraction.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct BarBeat {
+    pub bar: u32,
+    pub beat: f64,
+}
+
+/// One bar of the score.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bar {
+    /// One-based.
+    pub number: u32,
+    pub start_tick: u64,
+    pub end_tick: u64,
+    pub numerator: u8,
+    pub denominator: u32,
+}
+
+/// Time signatures over ticks, for converting between ticks and bars/beats.
+/// A change that does not fall on a bar line cuts the current bar short.
+#[derive(Clone, Debug)]
+pub struct MeterMap {
+    ticks_per_quarter: f64,
+    segments: Vec<MeterSegment>,
+}
+
+impl MeterMap {
+    /// Build from `(tick, numerator, denominator)` changes in any order.
+    /// Later changes at the same tick win, and 4/4 applies before the first.
+    /// Fails unless `ticks_per_quarter` is positive, since every bar and beat
+    /// length is a multiple of it.
+    pub fn new(ticks_per_quarter: f64, changes: &[(u64, u8, u32)]) -> AppResult<Self> {
+        if !(ticks_per_quarter.is_finite() && ticks_per_quarter > 0.0) {
+            return Err(AppError::InvalidArgument(format!(
+                "Ticks per quarter note must be positive, got {}",
+                ticks_per_quarter
+            )));
+        }
+        let mut sorted: Vec<(u64, u8, u32)> = changes
+            .iter()
+            .copied()
+            .filter(|(_, numerator, denominator)| *numerator > 0 && *denominator > 0)
+            .collect();
+        sorted.sort_by_key(|(tick, _, _)| *tick);
+
+        let mut map = MeterMap {
+            ticks_per_quarter,
+            segments: vec![MeterSegment {
+                tick: 0,
+                bar: 0,
+                numerator: 4,
+                denominator: 4,
+            }],
+        };
+        for (tick, numerator, denominator) in sorted {
+            let last = *map.segments.last().expect("meter map has a first segment");
+            if tick == last.tick {
+                let segment = map
+                    .segments
+                    .last_mut()
+                    .expect("meter map has a first segment");
+                segment.numerator = numerator;
+                segment.denominator = denominator;
+                continue;
+            }
+            let bars = ((tick - last.tick) as f64 / map.bar_ticks(&last)).ceil() as u32;
+            map.segments.push(MeterSegment {
+                tick,
+                bar: last.bar + bars,
+                numerator,
+                denominator,
+            });
+        }
+        Ok(map)
+    }
+
+    pub fn segments(&self) -> &[MeterSegment] {
+        &self.segments
+    }
+
+    fn beat_ticks(&self, segment: &MeterSegment) -> f64 {
+        self.ticks_per_quarter * 4.0 / segment.denominator as f64
+    }
+
+    fn bar_ticks(&self, segment: &MeterSegment) -> f64 {
+        self.beat_ticks(segment) * segment.numerator as f64
+    }
+
+    /// Bar and beat at a (fractional) tick.
+    pub fn position_at(&self, tick: f64) -> BarBeat {
+        let index = self
+            .segments
+            .partition_point(|segment| segment.tick as f64 <= tick);
+        let segment = &self.segments[index.saturating_sub(1)];
+        let offset = (tick - segment.tick as f64).max(0.0);
+        let bars = (offset / self.bar_ticks(segment)).floor();
+        BarBeat {
+            bar: segment.bar + bars as u32 + 1,
+            beat: (offset - bars * self.bar_ticks(segment)) / self.beat_ticks(segment) + 1.0,
+        }
+    }
+
+    /// Fractional tick of a bar/beat position; the inverse of `position_at`.
+    pub fn tick_at(&self, position: BarBeat) -> f64 {
+        let bar = position.bar.max(1) - 1;
+        let index = self.segments.partition_point(|segment| segment.bar <= bar);
+        let segment = &self.segments[index.saturating_sub(1)];
+        segment.tick as f64
+            + (bar - segment.bar) as f64 * self.bar_ticks(segment)
+            + (position.beat - 1.0) * self.beat_ticks(segment)
+    }
+
+    /// Every bar that starts before `end_tick` (at least one).
+    pub fn bars(&self, end_tick: u64) -> Vec<Bar> {
+        let mut bars = Vec::new();
+        for (index, segment) in self.segments.iter().enumerate() {
+            let next = self.segments.get(index + 1).map(|next| next.tick);
+            let length = self.bar_ticks(segment);
+            let mut start = segment.tick as f64;
+            loop {
+                let limit = next.unwrap_or(u64::MAX) as f64;
+                if start >= limit || (start >= end_tick as f64 && !bars.is_empty()) {
+                    break;
+                }
+                let end = (start + length).min(limit);
+                bars.push(Bar {
+                    number: bars.len() as u32 + 1,
+                    start_tick: start.round() as u64,
+                    end_tick: end.round() as u64,
+                    numerator: segment.numerator,
+                    denominator: segment.denominator,
+                });
+                start = end;
+            }
+            if next.is_none_or(|tick| tick >= end_tick) && !bars.is_empty() {
+                break;
+            }
+        }
+        bars
+    }
+}
+
+/// Frame containing `seconds` at `fps` (frame `n` covers `[n / fps, (n + 1) / fps)`).
+pub fn frame_at(seconds: f64, fps: f64) -> u64 {
+    // Absorb float error so that `frame_at(frame_start(n))` is `n`
+    ((seconds * fps) + 1e-6).floor().max(0.0) as u64
+}
+
+/// Time at which frame `frame` starts.
+pub fn frame_start(frame: u64, fps: f64) -> f64 {
+    frame as f64 / fps
+}
+
+/// Tempo and meter of one file: converts between ticks, seconds, bars/beats and video frames.
+#[derive(Clone, Debug)]
+pub struct Timeline {
+    pub tempo: TempoMap,
+    pub meter: MeterMap,
+}
+
+impl Timeline {
+    pub fn new(
+        base: TickBase,
+        tempos: &[(u64, u32)],
+        meters: &[(u64, u8, u32)],
+    ) -> AppResult<Self> {
+        Ok(Timeline {
+            tempo: TempoMap::new(base, tempos),
+            meter: MeterMap::new(base.ticks_per_quarter(), meters)?,
+        })
+    }
+
+    pub fn seconds_at(&self, tick: u64) -> f64 {
+        self.tempo.seconds_at(tick)
+    }
+
+    pub fn tick_at(&self, seconds: f64) -> f64 {
+        self.tempo.tick_at(seconds)
+    }
+
+    pub fn position_at_tick(&self, tick: f64) -> BarBeat {
+        self.meter.position_at(tick)
+    }
+
+    pub fn position_at_seconds(&self, seconds: f64) -> BarBeat {
+        self.meter.position_at(self.tempo.tick_at(seconds))
+    }
+
+    pub fn seconds_at_position(&self, position: BarBeat) -> f64 {
+        self.tempo
+            .seconds_at_fractional(self.meter.tick_at(position))
+    }
+
+    pub fn frame_at_tick(&self, tick: u64, fps: f64) -> u64 {
+        frame_at(self.tempo.seconds_at(tick), fps)
+    }
+
+    pub fn tick_at_frame(&self, frame: u64, fps: f64) -> f64 {
+        self.tempo.tick_at(frame_start(frame, fps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const PPQ: u16 = 480;
+
+    fn approx(a: f64, b: f64, tolerance: f64) -> bool {
+        (a - b).abs() <= tolerance * (1.0 + a.abs().max(b.abs()))
+    }
+
+    /// Tempo changes between 20 and 300 BPM.
+    fn tempos() -> impl Strategy<Value = Vec<(u64, u32)>> {
+        prop::collection::vec((0u64..200_000, 200_000u32..3_000_000), 0..12)
+    }
+
+    fn meters() -> impl Strategy<Value = Vec<(u64, u8, u32)>> {
+        prop::collection::vec(
+            (
+                0u64..200_000,
+                1u8..13,
+                prop::sample::select(vec![1u32, 2, 4, 8, 16]),
+            ),
+            0..8,
+        )
+    }
+
+    #[test]
+    fn constant_tempo_matches_formula() {
+        let map = TempoMap::new(TickBase::Metrical(PPQ), &[(0, 600_000)]);
+        // 100 BPM: one quarter is 0.6 s
+        assert!(approx(map.seconds_at(PPQ as u64 * 10), 6.0, 1e-12));
+        assert!(approx(map.tick_at(6.0), PPQ as f64 * 10.0, 1e-12));
+    }
+
+    #[test]
+    fn ritardando_slows_later_beats() {
+        // 120 BPM for two beats, then 60 BPM
+        let map = TempoMap::new(TickBase::Metrical(PPQ), &[(PPQ as u64 * 2, 1_000_000)]);
+        assert!(approx(map.seconds_at(PPQ as u64 * 2), 1.0, 1e-12));
+        assert!(approx(map.seconds_at(PPQ as u64 * 3), 2.0, 1e-12));
+    }
+
+    #[test]
+    fn timecode_ignores_tempo_events() {
+        let map = TempoMap::new(TickBase::Timecode(1000.0), &[(100, 1_000_000)]);
+        assert!(approx(map.seconds_at(2500), 2.5, 1e-12));
+    }
+
+    #[test]
+    fn meter_change_starts_new_bar() {
+        // Two bars of 4/4, then 3/4
+        let meter = MeterMap::new(PPQ as f64, &[(PPQ as u64 * 8, 3, 4)]).unwrap();
+        let bars = meter.bars(PPQ as u64 * 14);
+        assert_eq!(bars.len(), 4);
+        assert_eq!((bars[2].start_tick, bars[2].numerator), (PPQ as u64 * 8, 3));
+        assert_eq!(bars[3].end_tick, PPQ as u64 * 14);
+        assert_eq!(
+            meter.position_at(PPQ as f64 * 12.5),
+            BarBeat { bar: 4, beat: 2.5 }
+        );
+    }
+
+    #[test]
+    fn mid_bar_meter_change_cuts_the_bar_short() {
+        // 4/4 changed to 6/8 after six beats: bar 2 is only two beats long
+        let meter = MeterMap::new(PPQ as f64, &[(PPQ as u64 * 6, 6, 8)]).unwrap();
+        let bars = meter.bars(PPQ as u64 * 9);
+        assert_eq!(bars[1].end_tick - bars[1].start_tick, PPQ as u64 * 2);
+        assert_eq!(bars[2].start_tick, PPQ as u64 * 6);
+        assert_eq!(
+            meter.position_at(PPQ as f64 * 6.0),
+            BarBeat { bar: 3, beat: 1.0 }
+        );
+    }
+
+    #[test]
+    fn meter_map_refuses_a_non_positive_division() {
+        for ticks_per_quarter in [0.0, -480.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                MeterMap::new(ticks_per_quarter, &[]),
+                Err(AppError::InvalidArgument(_))
+            ));
+        }
+        assert!(Timeline::new(TickBase::Metrical(0), &[], &[]).is_err());
+        assert!(Timeline::new(TickBase::Timecode(0.0), &[], &[]).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn seconds_and_ticks_round_trip(changes in tempos(), tick in 0u64..400_000) {
+            let map = TempoMap::new(TickBase::Metrical(PPQ), &changes);
+            let seconds = map.seconds_at(tick);
+            prop_assert!(approx(map.tick_at(seconds), tick as f64, 1e-9));
+        }
+
+        #[test]
+        fn seconds_increase_with_ticks(changes in tempos(), a in 0u64..400_000, b in 0u64..400_000) {
+            let map = TempoMap::new(TickBase::Metrical(PPQ), &changes);
+            let (low, high) = (a.min(b), a.max(b));
+            prop_assert!(map.seconds_at(low) <= map.seconds_at(high));
+        }
+
+        #[test]
+        fn segments_are_continuous(changes in tempos()) {
+            let map = TempoMap::new(TickBase::Metrical(PPQ), &changes);
+            for pair in map.segments().windows(2) {
+                prop_assert!(pair[0].tick < pair[1].tick);
+                // The segment's own start time agrees with the previous tempo
+                let just_before = map.seconds_at_fractional(pair[1].tick as f64 - 1e-6);
+                prop_assert!(approx(just_before, pair[1].seconds, 1e-6));
+            }
+        }
+
+        #[test]
+        fn bar_beat_round_trip(changes in meters(), tick in 0u64..400_000) {
+            let meter = MeterMap::new(PPQ as f64, &changes).unwrap();
+            let position = meter.position_at(tick as f64);
+            prop_assert!(position.bar >= 1 && position.beat >= 1.0);
+            prop_assert!(approx(meter.tick_at(position), tick as f64, 1e-9));
+        }
+
+        #[test]
+        fn bars_tile_the_timeline(changes in meters(), end in 1u64..400_000) {
+            let meter = MeterMap::new(PPQ as f64, &changes).unwrap();
+            let bars = meter.bars(end);
+            prop_assert_eq!(bars[0].start_tick, 0);
+            prop_assert!(bars.last().unwrap().end_tick >= end);
+            for pair in bars.windows(2) {
+                prop_assert_eq!(pair[0].end_tick, pair[1].start_tick);
+                prop_assert_eq!(pair[1].number, pair[0].number + 1);
+            }
+            for bar in &bars {
+                prop_assert_eq!(meter.position_at(bar.start_tick as f64).bar, bar.number);
+            }
+        }
+
+        #[test]
+        fn frames_round_trip(frame in 0u64..1_000_000, fps in prop::sample::select(vec![24.0, 25.0, 30.0, 30_000.0 / 1001.0, 60.0])) {
+            prop_assert_eq!(frame_at(frame_start(frame, fps), fps), frame);
+        }
+
+        #[test]
+        fn positions_and_seconds_round_trip(tempo_changes in tempos(), meter_changes in meters(), tick in 0u64..400_000) {
+            let timeline = Timeline::new(TickBase::Metrical(PPQ), &tempo_changes, &meter_changes).unwrap();
+            let seconds = timeline.seconds_at(tick);
+            let position = timeline.position_at_seconds(seconds);
+            prop_assert!(approx(timeline.seconds_at_position(position), seconds, 1e-9));
+        }
+    }
+}
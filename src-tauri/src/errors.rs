@@ -0,0 +1,129 @@
+use std::path::Path;
+use std::process::ExitStatus;
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+/// Lines of FFmpeg stderr kept in `FfmpegFailed`; the rest is banner noise.
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Error returned by every Tauri command.
+///
+/// Serialized as `{ kind, message, ...details }` so the frontend can branch on
+/// `kind` (e.g. offer FFmpeg install help) and still show `message` verbatim.
+#[derive(Debug, PartialEq, thiserror::Error)]
+pub enum AppError {
+    #[error("FFmpeg is not installed. Please install FFmpeg first.")]
+    FfmpegMissing,
+    #[error("FFmpeg failed (exit code {}): {stderr_tail}", exit_code.map_or("none".to_string(), |c| c.to_string()))]
+    FfmpegFailed {
+        exit_code: Option<i32>,
+        stderr_tail: String,
+    },
+    #[error("{message}")]
+    Io {
+        path: Option<String>,
+        message: String,
+    },
+    #[error("The operation was cancelled")]
+    Cancelled,
+    #[error("Unsupported format: {0}")]
+    UnsupportedFormat(String),
+    #[error("{codec} cannot be stored in {container} (supported: {})", supported.join(", "))]
+    IncompatibleCodec {
+        container: String,
+        codec: String,
+        supported: Vec<String>,
+    },
+    #[error("Failed to decode audio: {0}")]
+    Decode(String),
+    #[error("Invalid project file: {0}")]
+    InvalidProject(String),
+    #[error("{0}")]
+    InvalidArgument(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            AppError::FfmpegMissing => "FfmpegMissing",
+            AppError::FfmpegFailed { .. } => "FfmpegFailed",
+            AppError::Io { .. } => "Io",
+            AppError::Cancelled => "Cancelled",
+            AppError::UnsupportedFormat(_) => "UnsupportedFormat",
+            AppError::IncompatibleCodec { .. } => "IncompatibleCodec",
+            AppError::Decode(_) => "Decode",
+            AppError::InvalidProject(_) => "InvalidProject",
+            AppError::InvalidArgument(_) => "InvalidArgument",
+            AppError::NotFound(_) => "NotFound",
+            AppError::Internal(_) => "Internal",
+        }
+    }
+
+    /// I/O failure on a specific file.
+    pub fn io(path: &Path, action: &str, error: std::io::Error) -> Self {
+        AppError::Io {
+            path: Some(path.to_string_lossy().into_owned()),
+            message: format!("Failed to {} {}: {}", action, path.display(), error),
+        }
+    }
+
+    /// Error for a failed FFmpeg spawn: a missing binary is reported as such.
+    pub fn ffmpeg_spawn(error: std::io::Error) -> Self {
+        if error.kind() == std::io::ErrorKind::NotFound {
+            AppError::FfmpegMissing
+        } else {
+            AppError::Io {
+                path: None,
+                message: format!("Failed to execute FFmpeg: {}", error),
+            }
+        }
+    }
+
+    /// FFmpeg exited unsuccessfully; keep only the end of its stderr.
+    pub fn ffmpeg_failed(status: ExitStatus, stderr: &str) -> Self {
+        let lines: Vec<&str> = stderr.trim_end().lines().collect();
+        let tail = lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..].join("\n");
+        AppError::FfmpegFailed {
+            exit_code: status.code(),
+            stderr_tail: tail,
+        }
+    }
+}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AppError", 5)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        match self {
+            AppError::FfmpegFailed {
+                exit_code,
+                stderr_tail,
+            } => {
+                state.serialize_field("exitCode", exit_code)?;
+                state.serialize_field("stderrTail", stderr_tail)?;
+            }
+            AppError::Io { path, .. } => {
+                state.serialize_field("path", path)?;
+            }
+            AppError::IncompatibleCodec {
+                container,
+                codec,
+                supported,
+            } => {
+                state.serialize_field("container", container)?;
+                state.serialize_field("codec", codec)?;
+                state.serialize_field("supported", supported)?;
+            }
+            _ => {}
+        }
+        state.end()
+    }
+}
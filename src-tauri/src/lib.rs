@@ -1,4 +1,15 @@
-use std::process::Command;
+mod commands;
+mod domain;
+mod errors;
+mod services;
+
+use services::audio_store::AudioStore;
+use services::ffmpeg::jobs::ConversionJobs;
+use services::midi_store::MidiStore;
+use services::peak_cache::PeakCache;
+use services::render_store::RenderStore;
+use services::settings::SettingsStore;
+use tauri::Manager;
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
@@ -6,59 +17,6 @@ fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
 
-#[tauri::command]
-async fn convert_video(input_path: String, output_format: String) -> Result<String, String> {
-    // Check if FFmpeg is installed
-    let ffmpeg_check = Command::new("ffmpeg")
-        .arg("-version")
-        .output();
-
-    if ffmpeg_check.is_err() {
-        return Err("FFmpeg is not installed. Please install FFmpeg first.".to_string());
-    }
-
-    // Determine output path
-    let output_path = input_path.replace(".webm", &format!(".{}", output_format));
-
-    // Run FFmpeg conversion
-    let output = Command::new("ffmpeg")
-        .arg("-i")
-        .arg(&input_path)
-        .arg("-c:v")
-        .arg("libx264")
-        .arg("-preset")
-        .arg("medium")
-        .arg("-crf")
-        .arg("23")
-        .arg("-c:a")
-        .arg("aac")
-        .arg("-b:a")
-        .arg("192k")
-        .arg("-y") // Overwrite output file if exists
-        .arg(&output_path)
-        .output();
-
-    match output {
-        Ok(result) => {
-            if result.status.success() {
-                Ok(output_path)
-            } else {
-                let error_msg = String::from_utf8_lossy(&result.stderr);
-                Err(format!("FFmpeg conversion failed: {}", error_msg))
-            }
-        }
-        Err(e) => Err(format!("Failed to execute FFmpeg: {}", e)),
-    }
-}
-
-#[tauri::command]
-async fn check_ffmpeg_installed() -> Result<bool, String> {
-    match Command::new("ffmpeg").arg("-version").output() {
-        Ok(output) => Ok(output.status.success()),
-        Err(_) => Ok(false),
-    }
-}
-
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
@@ -66,7 +24,48 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_shell::init())
-        .invoke_handler(tauri::generate_handler![greet, convert_video, check_ffmpeg_installed])
+        .manage(AudioStore::default())
+        .manage(MidiStore::default())
+        .manage(RenderStore::default())
+        .manage(ConversionJobs::default())
+        .manage(PeakCache::default())
+        .setup(|app| {
+            let config_dir = app.path().app_config_dir()?;
+            app.manage(SettingsStore::load(&config_dir));
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            greet,
+            commands::export::convert_video,
+            commands::export::cancel_conversion,
+            commands::export::check_ffmpeg_installed,
+            commands::audio::decode_audio,
+            commands::audio::read_decoded_audio,
+            commands::audio::release_decoded_audio,
+            commands::audio::analyze_spectrum,
+            commands::audio::extract_features,
+            commands::audio::compute_waveform_peaks,
+            commands::audio::compute_spectrogram,
+            commands::audio::export_spectrogram_image,
+            commands::audio::analyze_rhythm,
+            commands::audio::analyze_loudness,
+            commands::audio::analyze_chroma,
+            commands::audio::track_pitch,
+            commands::colormap::get_colormap_lut,
+            commands::export::start_render,
+            commands::export::push_frame,
+            commands::export::finish_render,
+            commands::export::cancel_render,
+            commands::midi::parse_midi,
+            commands::midi::load_midi,
+            commands::midi::midi_notes_in_window,
+            commands::midi::release_midi,
+            commands::midi::synthesize_midi,
+            commands::project::save_project,
+            commands::project::load_project,
+            commands::settings::get_settings,
+            commands::settings::update_settings
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
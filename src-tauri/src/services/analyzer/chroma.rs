@@ -0,0 +1,305 @@
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use serde::{Deserialize, Serialize};
+
+use super::fill_frame;
+use super::window::WindowFunction;
+use crate::errors::{AppError, AppResult};
+
+/// Pitches folded into the chroma, roughly E2 to B7. Lower notes are not
+/// resolved by the FFT and higher ones are mostly overtones.
+const MIN_FREQ: f32 = 80.0;
+const MAX_FREQ: f32 = 4000.0;
+
+/// Krumhansl–Kessler key profiles, tonic first.
+const MAJOR_PROFILE: [f64; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+const MINOR_PROFILE: [f64; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+/// Tonic spellings as keys are usually written.
+const MAJOR_TONICS: [&str; 12] = [
+    "C", "Db", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B",
+];
+const MINOR_TONICS: [&str; 12] = [
+    "C", "C#", "D", "Eb", "E", "F", "F#", "G", "G#", "A", "Bb", "B",
+];
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ChromaOptions {
+    pub window_size: usize,
+    pub hop_size: usize,
+    /// Seconds of audio behind each key segment.
+    pub segment_length: f64,
+}
+
+impl Default for ChromaOptions {
+    fn default() -> Self {
+        ChromaOptions {
+            window_size: 8192,
+            hop_size: 2048,
+            segment_length: 8.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyMode {
+    Major,
+    Minor,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyEstimate {
+    /// Pitch class of the tonic, 0 = C.
+    pub tonic: u8,
+    pub mode: KeyMode,
+    /// e.g. `"Eb major"`, `"F# minor"`.
+    pub name: String,
+    /// Correlation of the chroma with the key profile, 0–1.
+    pub confidence: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeySegment {
+    pub start: f64,
+    pub end: f64,
+    #[serde(flatten)]
+    pub key: KeyEstimate,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Chromagram {
+    pub sample_rate: u32,
+    pub hop_size: usize,
+    pub frame_count: usize,
+    /// Frame `i` is centred at `i * frame_duration` seconds.
+    pub frame_duration: f64,
+    /// `frame_count * 12` values from C to B, frame-major, each frame scaled
+    /// so its strongest pitch class is 255 (silent frames are all 0).
+    pub values: Vec<u8>,
+    /// Key of the whole file; `None` when it is silent.
+    pub key: Option<KeyEstimate>,
+    /// Runs of `segment_length` windows that share a key, in time order.
+    pub segments: Vec<KeySegment>,
+}
+
+fn pearson(a: &[f64; 12], b: &[f64; 12]) -> f64 {
+    let mean_a = a.iter().sum::<f64>() / 12.0;
+    let mean_b = b.iter().sum::<f64>() / 12.0;
+    let (mut covariance, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        covariance += (x - mean_a) * (y - mean_b);
+        var_a += (x - mean_a).powi(2);
+        var_b += (y - mean_b).powi(2);
+    }
+    if var_a <= 0.0 || var_b <= 0.0 {
+        0.0
+    } else {
+        covariance / (var_a * var_b).sqrt()
+    }
+}
+
+/// Best of the 24 major and minor keys for a pitch-class distribution.
+fn estimate_key(chroma: &[f64; 12]) -> Option<KeyEstimate> {
+    if chroma.iter().all(|&v| v <= 0.0) {
+        return None;
+    }
+    let mut best: Option<(f64, u8, KeyMode)> = None;
+    for tonic in 0..12u8 {
+        // Rotate so the candidate tonic is first, like the profiles
+        let rotated: [f64; 12] = std::array::from_fn(|i| chroma[(i + tonic as usize) % 12]);
+        for (mode, profile) in [
+            (KeyMode::Major, &MAJOR_PROFILE),
+            (KeyMode::Minor, &MINOR_PROFILE),
+        ] {
+            let score = pearson(&rotated, profile);
+            if best.is_none_or(|(value, _, _)| score > value) {
+                best = Some((score, tonic, mode));
+            }
+        }
+    }
+    best.map(|(score, tonic, mode)| KeyEstimate {
+        tonic,
+        mode,
+        name: match mode {
+            KeyMode::Major => format!("{} major", MAJOR_TONICS[tonic as usize]),
+            KeyMode::Minor => format!("{} minor", MINOR_TONICS[tonic as usize]),
+        },
+        confidence: score.clamp(0.0, 1.0),
+    })
+}
+
+/// Sum of the per-frame chroma of `frames`.
+fn total(chroma: &[[f64; 12]]) -> [f64; 12] {
+    chroma.iter().fold([0.0; 12], |mut sum, frame| {
+        sum.iter_mut().zip(frame).for_each(|(s, v)| *s += v);
+        sum
+    })
+}
+
+/// Chromagram of a mono signal, the key of the whole file and the key of
+/// each `segment_length` stretch (adjacent stretches in the same key merged).
+pub fn analyze(
+    samples: &[f32],
+    sample_rate: u32,
+    options: &ChromaOptions,
+) -> AppResult<Chromagram> {
+    let size = options.window_size;
+    if !size.is_power_of_two() || !(1024..=32768).contains(&size) {
+        return Err(AppError::InvalidArgument(format!(
+            "windowSize must be a power of two between 1024 and 32768, got {}",
+            size
+        )));
+    }
+    if options.hop_size == 0 {
+        return Err(AppError::InvalidArgument(
+            "hopSize must be positive".to_string(),
+        ));
+    }
+    if !options.segment_length.is_finite() || options.segment_length <= 0.0 {
+        return Err(AppError::InvalidArgument(
+            "segmentLength must be positive".to_string(),
+        ));
+    }
+
+    // Pitch class of every FFT bin in range
+    let bin_hz = sample_rate as f32 / size as f32;
+    let classes: Vec<(usize, usize)> = (1..size / 2)
+        .filter_map(|k| {
+            let freq = k as f32 * bin_hz;
+            (MIN_FREQ..=MAX_FREQ).contains(&freq).then(|| {
+                let midi = 69.0 + 12.0 * (freq / 440.0).log2();
+                (k, (midi.round() as i32).rem_euclid(12) as usize)
+            })
+        })
+        .collect();
+
+    let window = WindowFunction::Hann.coefficients(size);
+    let fft = FftPlanner::<f32>::new().plan_fft_forward(size);
+    let mut frame = vec![0.0f32; size];
+    let mut buffer = vec![Complex::new(0.0f32, 0.0); size];
+
+    let frame_count = samples.len().div_ceil(options.hop_size);
+    let mut chroma: Vec<[f64; 12]> = Vec::with_capacity(frame_count);
+    for i in 0..frame_count {
+        fill_frame(samples, i * options.hop_size + size / 2, size, &mut frame);
+        for ((slot, sample), w) in buffer.iter_mut().zip(&frame).zip(&window) {
+            *slot = Complex::new(sample * w, 0.0);
+        }
+        fft.process(&mut buffer);
+        let mut bins = [0.0f64; 12];
+        for &(k, class) in &classes {
+            bins[class] += buffer[k].norm() as f64;
+        }
+        // Each frame counts the same towards the key, however loud it is
+        let max = bins.iter().copied().fold(0.0, f64::max);
+        if max > 1e-6 {
+            bins.iter_mut().for_each(|v| *v /= max);
+        } else {
+            bins = [0.0; 12];
+        }
+        chroma.push(bins);
+    }
+
+    let frame_duration = options.hop_size as f64 / sample_rate as f64;
+    let frames_per_segment = ((options.segment_length / frame_duration).round() as usize).max(1);
+    let duration = samples.len() as f64 / sample_rate as f64;
+    let mut segments: Vec<KeySegment> = Vec::new();
+    for (index, frames) in chroma.chunks(frames_per_segment).enumerate() {
+        let start = (index * frames_per_segment) as f64 * frame_duration;
+        let end = (start + frames.len() as f64 * frame_duration).min(duration);
+        let Some(key) = estimate_key(&total(frames)) else {
+            continue;
+        };
+        match segments.last_mut() {
+            Some(last)
+                if last.key.tonic == key.tonic
+                    && last.key.mode == key.mode
+                    && last.end >= start =>
+            {
+                // Confidence of a merged segment is weighted by length
+                let (a, b) = (last.end - last.start, end - start);
+                last.key.confidence = (last.key.confidence * a + key.confidence * b) / (a + b);
+                last.end = end;
+            }
+            _ => segments.push(KeySegment { start, end, key }),
+        }
+    }
+
+    let values = chroma
+        .iter()
+        .flat_map(|frame| frame.iter().map(|v| (v * 255.0).round() as u8))
+        .collect();
+
+    Ok(Chromagram {
+        sample_rate,
+        hop_size: options.hop_size,
+        frame_count,
+        frame_duration,
+        values,
+        key: estimate_key(&total(&chroma)),
+        segments,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 22_050;
+
+    /// Equal-tempered sines for the MIDI `notes`, sounding together.
+    fn chord(notes: &[u8], seconds: f64) -> Vec<f32> {
+        (0..(seconds * SAMPLE_RATE as f64) as usize)
+            .map(|n| {
+                let t = n as f64 / SAMPLE_RATE as f64;
+                notes
+                    .iter()
+                    .map(|&note| {
+                        let hz = 440.0 * 2f64.powf((note as f64 - 69.0) / 12.0);
+                        (0.2 * (2.0 * std::f64::consts::PI * hz * t).sin()) as f32
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn c_major_triad_is_c_major() {
+        let chromagram = analyze(
+            &chord(&[60, 64, 67], 4.0),
+            SAMPLE_RATE,
+            &ChromaOptions::default(),
+        )
+        .unwrap();
+        let key = chromagram.key.unwrap();
+        assert_eq!((key.tonic, key.mode), (0, KeyMode::Major));
+        assert_eq!(key.name, "C major");
+
+        // C, E and G dominate a frame in the middle
+        let middle = chromagram.frame_count / 2;
+        let frame = &chromagram.values[middle * 12..(middle + 1) * 12];
+        for (pitch_class, &value) in frame.iter().enumerate() {
+            if [0, 4, 7].contains(&pitch_class) {
+                assert!(value > 128, "{:?}", frame);
+            } else {
+                assert!(value < 128, "{:?}", frame);
+            }
+        }
+    }
+
+    #[test]
+    fn silence_has_no_key() {
+        let chromagram = analyze(&[0.0; 44_100], SAMPLE_RATE, &ChromaOptions::default()).unwrap();
+        assert!(chromagram.key.is_none() && chromagram.segments.is_empty());
+        assert!(chromagram.values.iter().all(|&value| value == 0));
+    }
+}
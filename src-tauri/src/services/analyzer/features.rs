@@ -0,0 +1,251 @@
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use serde::{Deserialize, Serialize};
+
+use super::window::WindowFunction;
+use super::{fill_frame, frame_ends};
+use crate::errors::{AppError, AppResult};
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FeatureOptions {
+    pub fft_size: usize,
+    /// One value per video frame at this rate, like `analyze_spectrum`.
+    pub frame_rate: Option<f64>,
+    /// Hop in samples, used when `frame_rate` is not given (defaults to `fft_size / 2`).
+    pub hop: Option<usize>,
+    /// Edges of the low / mid / high bands in Hz.
+    pub low_cutoff: f32,
+    pub high_cutoff: f32,
+    /// Share of the spectral energy below the rolloff frequency.
+    pub rolloff: f32,
+}
+
+impl Default for FeatureOptions {
+    fn default() -> Self {
+        FeatureOptions {
+            fft_size: 2048,
+            frame_rate: None,
+            hop: None,
+            low_cutoff: 250.0,
+            high_cutoff: 4000.0,
+            rolloff: 0.85,
+        }
+    }
+}
+
+/// Per-frame descriptors, each curve `frame_count` long. Frame `i` ends at
+/// `frame_times[i]`, the same instants as `analyze_spectrum` with the same
+/// `frame_rate`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AudioFeatures {
+    pub sample_rate: u32,
+    pub fft_size: usize,
+    pub frame_count: usize,
+    pub frame_times: Vec<f64>,
+    /// RMS of the frame's samples (1.0 = full-scale square wave).
+    pub rms: Vec<f32>,
+    /// Spectral centroid in Hz (0 for silence).
+    pub centroid: Vec<f32>,
+    /// Frequency in Hz below which `rolloff` of the energy lies.
+    pub rolloff: Vec<f32>,
+    /// Spectral flatness, 0 (tonal) to 1 (white noise).
+    pub flatness: Vec<f32>,
+    /// Positive change of the magnitude spectrum since the previous frame.
+    pub flux: Vec<f32>,
+    /// Share of adjacent samples that change sign, 0–1.
+    pub zcr: Vec<f32>,
+    /// Mean power of the low, mid and high bands.
+    pub low: Vec<f32>,
+    pub mid: Vec<f32>,
+    pub high: Vec<f32>,
+}
+
+/// RMS, spectral shape (centroid, rolloff, flatness, flux), zero-crossing
+/// rate and three band energies of a mono signal, one value per frame.
+pub fn extract(
+    samples: &[f32],
+    sample_rate: u32,
+    options: &FeatureOptions,
+) -> AppResult<AudioFeatures> {
+    let fft_size = options.fft_size;
+    if !fft_size.is_power_of_two() || !(256..=32768).contains(&fft_size) {
+        return Err(AppError::InvalidArgument(format!(
+            "fftSize must be a power of two between 256 and 32768, got {}",
+            fft_size
+        )));
+    }
+    let nyquist = sample_rate as f32 / 2.0;
+    if !(0.0 < options.low_cutoff
+        && options.low_cutoff < options.high_cutoff
+        && options.high_cutoff < nyquist)
+    {
+        return Err(AppError::InvalidArgument(format!(
+            "Band edges must satisfy 0 < lowCutoff < highCutoff < {}, got {} and {}",
+            nyquist, options.low_cutoff, options.high_cutoff
+        )));
+    }
+    if !(0.0 < options.rolloff && options.rolloff < 1.0) {
+        return Err(AppError::InvalidArgument(
+            "rolloff must be between 0 and 1".to_string(),
+        ));
+    }
+
+    let bin_count = fft_size / 2;
+    let bin_hz = sample_rate as f32 / fft_size as f32;
+    let hop = options.hop.unwrap_or(bin_count);
+    let ends = frame_ends(samples.len(), sample_rate, hop, options.frame_rate);
+    let band = |k: usize| {
+        let freq = k as f32 * bin_hz;
+        if freq < options.low_cutoff {
+            0
+        } else if freq < options.high_cutoff {
+            1
+        } else {
+            2
+        }
+    };
+    let mut band_sizes = [0.0f32; 3];
+    (1..bin_count).for_each(|k| band_sizes[band(k)] += 1.0);
+
+    let window = WindowFunction::Hann.coefficients(fft_size);
+    let fft = FftPlanner::<f32>::new().plan_fft_forward(fft_size);
+    let mut frame = vec![0.0f32; fft_size];
+    let mut buffer = vec![Complex::new(0.0f32, 0.0); fft_size];
+    let mut magnitudes = vec![0.0f32; bin_count];
+    let mut previous = vec![0.0f32; bin_count];
+
+    let count = ends.len();
+    let mut features = AudioFeatures {
+        sample_rate,
+        fft_size,
+        frame_count: count,
+        frame_times: ends
+            .iter()
+            .map(|&end| end as f64 / sample_rate as f64)
+            .collect(),
+        rms: Vec::with_capacity(count),
+        centroid: Vec::with_capacity(count),
+        rolloff: Vec::with_capacity(count),
+        flatness: Vec::with_capacity(count),
+        flux: Vec::with_capacity(count),
+        zcr: Vec::with_capacity(count),
+        low: Vec::with_capacity(count),
+        mid: Vec::with_capacity(count),
+        high: Vec::with_capacity(count),
+    };
+
+    for &end in &ends {
+        fill_frame(samples, end, fft_size, &mut frame);
+        let square_sum: f32 = frame.iter().map(|s| s * s).sum();
+        features.rms.push((square_sum / fft_size as f32).sqrt());
+        let crossings = frame
+            .windows(2)
+            .filter(|pair| (pair[0] >= 0.0) != (pair[1] >= 0.0))
+            .count();
+        features.zcr.push(crossings as f32 / (fft_size - 1) as f32);
+
+        for ((slot, sample), w) in buffer.iter_mut().zip(&frame).zip(&window) {
+            *slot = Complex::new(sample * w, 0.0);
+        }
+        fft.process(&mut buffer);
+        for (k, magnitude) in magnitudes.iter_mut().enumerate() {
+            *magnitude = buffer[k].norm() / fft_size as f32;
+        }
+
+        // DC is left out of every spectral descriptor
+        let spectrum = &magnitudes[1..];
+        let total: f32 = spectrum.iter().sum();
+        let energy: f32 = spectrum.iter().map(|m| m * m).sum();
+        if total > 1e-9 {
+            let weighted: f32 = spectrum
+                .iter()
+                .enumerate()
+                .map(|(i, m)| (i + 1) as f32 * bin_hz * m)
+                .sum();
+            features.centroid.push(weighted / total);
+
+            let threshold = options.rolloff * energy;
+            let mut running = 0.0;
+            let rolloff_bin = spectrum
+                .iter()
+                .position(|m| {
+                    running += m * m;
+                    running >= threshold
+                })
+                .unwrap_or(spectrum.len() - 1);
+            features.rolloff.push((rolloff_bin + 1) as f32 * bin_hz);
+
+            // Geometric over arithmetic mean of the power spectrum
+            let log_mean =
+                spectrum.iter().map(|m| (m * m + 1e-12).ln()).sum::<f32>() / spectrum.len() as f32;
+            features
+                .flatness
+                .push((log_mean.exp() / (energy / spectrum.len() as f32)).min(1.0));
+        } else {
+            features.centroid.push(0.0);
+            features.rolloff.push(0.0);
+            features.flatness.push(0.0);
+        }
+
+        let flux: f32 = magnitudes
+            .iter()
+            .zip(&previous)
+            .map(|(m, p)| (m - p).max(0.0))
+            .sum();
+        features.flux.push(flux);
+        previous.copy_from_slice(&magnitudes);
+
+        let mut bands = [0.0f32; 3];
+        for (k, magnitude) in magnitudes.iter().enumerate().skip(1) {
+            bands[band(k)] += magnitude * magnitude;
+        }
+        let [low, mid, high] = std::array::from_fn(|b| bands[b] / band_sizes[b].max(1.0));
+        features.low.push(low);
+        features.mid.push(mid);
+        features.high.push(high);
+    }
+
+    Ok(features)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 44_100;
+
+    #[test]
+    fn silence_is_zero_without_nan() {
+        let features = extract(&[0.0; 44_100], SAMPLE_RATE, &FeatureOptions::default()).unwrap();
+        assert!(features.frame_count > 0);
+        assert!(features.rms.iter().all(|&rms| rms == 0.0));
+        for curve in [
+            &features.centroid,
+            &features.rolloff,
+            &features.flatness,
+            &features.flux,
+            &features.zcr,
+            &features.low,
+            &features.mid,
+            &features.high,
+        ] {
+            assert!(curve.iter().all(|value| value.is_finite()));
+        }
+    }
+
+    #[test]
+    fn sine_has_its_rms_and_centroid() {
+        // 1 kHz is in the default mid band (250 Hz to 4 kHz)
+        let samples: Vec<f32> = (0..44_100)
+            .map(|n| 0.5 * (2.0 * std::f32::consts::PI * 1000.0 * n as f32 / 44_100.0).sin())
+            .collect();
+        let features = extract(&samples, SAMPLE_RATE, &FeatureOptions::default()).unwrap();
+        let middle = features.frame_count / 2;
+        assert!((features.rms[middle] - 0.5 / 2f32.sqrt()).abs() < 0.01);
+        assert!((features.centroid[middle] - 1000.0).abs() < 30.0);
+        assert!(features.flatness[middle] < 0.01);
+        assert!(features.mid[middle] > 100.0 * features.low[middle].max(features.high[middle]));
+    }
+}
@@ -0,0 +1,348 @@
+use serde::Serialize;
+
+use crate::errors::{AppError, AppResult};
+
+/// Loudness is measured over 100 ms blocks; momentary and short-term windows
+/// are 4 and 30 of them (ITU-R BS.1770-4, EBU Tech 3341).
+const BLOCKS_PER_SECOND: usize = 10;
+const MOMENTARY_BLOCKS: usize = 4;
+const SHORT_TERM_BLOCKS: usize = 30;
+const ABSOLUTE_GATE: f64 = -70.0;
+const INTEGRATED_RELATIVE_GATE: f64 = -10.0;
+const RANGE_RELATIVE_GATE: f64 = -20.0;
+/// Taps per phase of the true-peak interpolation filter.
+const TRUE_PEAK_TAPS: usize = 12;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoudnessAnalysis {
+    /// Integrated loudness in LUFS; `None` when the whole file is below the absolute gate.
+    pub integrated: Option<f64>,
+    /// Loudness range (LRA) in LU.
+    pub loudness_range: f64,
+    /// Highest inter-sample peak in dBTP; `None` for digital silence.
+    pub true_peak: Option<f64>,
+    pub sample_peak: Option<f64>,
+    /// Seconds between curve points.
+    pub interval: f64,
+    /// Momentary loudness (400 ms) in LUFS, point `i` for the window ending at
+    /// `(i + 1) * interval`. Silence reads as -70.
+    pub momentary: Vec<f32>,
+    /// Short-term loudness (3 s), laid out like `momentary`.
+    pub short_term: Vec<f32>,
+}
+
+/// Direct form I biquad on f64 state.
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+        Biquad {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[1] * self.y[0]
+            - self.a[2] * self.y[1];
+        self.x = [input, self.x[0]];
+        self.y = [output, self.y[0]];
+        output
+    }
+}
+
+/// The two K-weighting stages (high shelf, then high-pass) of BS.1770,
+/// designed for `sample_rate` rather than tabulated for 48 kHz.
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+    let rate = sample_rate as f64;
+
+    let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let vh = 10f64.powf(gain / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    let (f0, q) = (38.13547087602444, 0.5003270373238773);
+    let k = (std::f64::consts::PI * f0 / rate).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    [shelf, high_pass]
+}
+
+/// BS.1770 channel weights: surrounds count +1.5 dB and the LFE not at all,
+/// assuming the usual L, R, C, (LFE,) Ls, Rs order.
+fn channel_weights(channels: usize) -> Vec<f64> {
+    match channels {
+        5 => vec![1.0, 1.0, 1.0, 1.41, 1.41],
+        6 => vec![1.0, 1.0, 1.0, 0.0, 1.41, 1.41],
+        n => vec![1.0; n],
+    }
+}
+
+fn to_lufs(mean_square: f64) -> f64 {
+    -0.691 + 10.0 * mean_square.log10()
+}
+
+/// Weighted K-filtered energy of each complete 100 ms block, summed over channels.
+fn block_energies(channels: &[Vec<f32>], sample_rate: u32) -> Vec<f64> {
+    let frames = channels.iter().map(Vec::len).min().unwrap_or(0);
+    let count = frames * BLOCKS_PER_SECOND / sample_rate as usize;
+    let boundary = |block: usize| block * sample_rate as usize / BLOCKS_PER_SECOND;
+    let mut energies = vec![0.0f64; count];
+    for (channel, weight) in channels.iter().zip(channel_weights(channels.len())) {
+        if weight == 0.0 {
+            continue;
+        }
+        let [mut shelf, mut high_pass] = k_weighting(sample_rate);
+        for (block, energy) in energies.iter_mut().enumerate() {
+            let sum: f64 = channel[boundary(block)..boundary(block + 1)]
+                .iter()
+                .map(|&sample| high_pass.process(shelf.process(sample as f64)).powi(2))
+                .sum();
+            *energy += weight * sum;
+        }
+    }
+    energies
+}
+
+/// Mean-square loudness of every window of `length` blocks ending at each block,
+/// treating blocks before the start as silence.
+fn windowed(energies: &[f64], length: usize, samples_per_block: f64) -> Vec<f64> {
+    let mut running = 0.0;
+    energies
+        .iter()
+        .enumerate()
+        .map(|(i, energy)| {
+            running += energy;
+            if i >= length {
+                running -= energies[i - length];
+            }
+            running.max(0.0) / (length as f64 * samples_per_block)
+        })
+        .collect()
+}
+
+/// Two-stage gated mean (absolute, then `relative` LU below the ungated mean).
+fn gated(blocks: &[f64], relative: f64) -> Vec<f64> {
+    let above_absolute: Vec<f64> = blocks
+        .iter()
+        .copied()
+        .filter(|&m| to_lufs(m) > ABSOLUTE_GATE)
+        .collect();
+    if above_absolute.is_empty() {
+        return Vec::new();
+    }
+    let mean = above_absolute.iter().sum::<f64>() / above_absolute.len() as f64;
+    let threshold = to_lufs(mean) + relative;
+    above_absolute
+        .into_iter()
+        .filter(|&m| to_lufs(m) > threshold)
+        .collect()
+}
+
+fn percentile(sorted: &[f64], fraction: f64) -> f64 {
+    let position = fraction * (sorted.len() - 1) as f64;
+    let (low, high) = (position.floor() as usize, position.ceil() as usize);
+    sorted[low] + (sorted[high] - sorted[low]) * (position - low as f64)
+}
+
+/// Highest absolute value of the signal upsampled to at least 192 kHz
+/// (4x below 96 kHz) with a Hann-windowed sinc interpolator.
+fn true_peak(channel: &[f32], sample_rate: u32) -> f32 {
+    let factor = match sample_rate {
+        0..=95_999 => 4,
+        96_000..=191_999 => 2,
+        _ => 1,
+    };
+    let half = (TRUE_PEAK_TAPS / 2) as isize;
+    // Phase p interpolates between x[n] and x[n + 1], at n + p / factor
+    let phases: Vec<Vec<f32>> = (1..factor)
+        .map(|p| {
+            let fraction = p as f64 / factor as f64;
+            (1 - half..=half)
+                .map(|m| {
+                    let t = fraction - m as f64;
+                    let sinc = (std::f64::consts::PI * t).sin() / (std::f64::consts::PI * t);
+                    let window = 0.5 * (1.0 + (std::f64::consts::PI * t / half as f64).cos());
+                    (sinc * window) as f32
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut peak = channel
+        .iter()
+        .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+    let sample = |index: isize| {
+        if index >= 0 {
+            channel.get(index as usize).copied().unwrap_or(0.0)
+        } else {
+            0.0
+        }
+    };
+    for n in 0..channel.len() as isize {
+        for taps in &phases {
+            let value: f32 = taps
+                .iter()
+                .zip(1 - half..=half)
+                .map(|(tap, m)| tap * sample(n + m))
+                .sum();
+            peak = peak.max(value.abs());
+        }
+    }
+    peak
+}
+
+fn decibels(amplitude: f32) -> Option<f64> {
+    (amplitude > 0.0).then(|| 20.0 * (amplitude as f64).log10())
+}
+
+/// Integrated loudness, loudness range, true peak and the momentary and
+/// short-term loudness curves of a multichannel signal (EBU R128).
+pub fn measure(channels: &[Vec<f32>], sample_rate: u32) -> AppResult<LoudnessAnalysis> {
+    if channels.is_empty() {
+        return Err(AppError::InvalidArgument(
+            "Audio has no channels".to_string(),
+        ));
+    }
+    if sample_rate < 8_000 {
+        return Err(AppError::InvalidArgument(format!(
+            "sampleRate must be at least 8000, got {}",
+            sample_rate
+        )));
+    }
+
+    let energies = block_energies(channels, sample_rate);
+    let samples_per_block = sample_rate as f64 / BLOCKS_PER_SECOND as f64;
+    let momentary = windowed(&energies, MOMENTARY_BLOCKS, samples_per_block);
+    let short_term = windowed(&energies, SHORT_TERM_BLOCKS, samples_per_block);
+    let curve = |values: &[f64]| {
+        values
+            .iter()
+            .map(|&m| to_lufs(m).max(ABSOLUTE_GATE) as f32)
+            .collect()
+    };
+
+    // Gating blocks: every complete 400 ms window, overlapping by 75%
+    let integrated_blocks = gated(
+        momentary.get(MOMENTARY_BLOCKS - 1..).unwrap_or(&[]),
+        INTEGRATED_RELATIVE_GATE,
+    );
+    let integrated = (!integrated_blocks.is_empty())
+        .then(|| to_lufs(integrated_blocks.iter().sum::<f64>() / integrated_blocks.len() as f64));
+
+    let mut range_blocks: Vec<f64> = gated(
+        short_term.get(SHORT_TERM_BLOCKS - 1..).unwrap_or(&[]),
+        RANGE_RELATIVE_GATE,
+    )
+    .into_iter()
+    .map(to_lufs)
+    .collect();
+    range_blocks.sort_by(f64::total_cmp);
+    let loudness_range = if range_blocks.is_empty() {
+        0.0
+    } else {
+        percentile(&range_blocks, 0.95) - percentile(&range_blocks, 0.10)
+    };
+
+    let sample_peak = channels
+        .iter()
+        .flat_map(|channel| channel.iter())
+        .fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+    let true_peak = channels
+        .iter()
+        .map(|channel| true_peak(channel, sample_rate))
+        .fold(0.0f32, f32::max);
+
+    Ok(LoudnessAnalysis {
+        integrated,
+        loudness_range,
+        true_peak: decibels(true_peak),
+        sample_peak: decibels(sample_peak),
+        interval: 1.0 / BLOCKS_PER_SECOND as f64,
+        momentary: curve(&momentary),
+        short_term: curve(&short_term),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    /// A 1 kHz sine for each `(seconds, dBFS)` section, the same in both channels.
+    fn stereo_sine(sections: &[(f64, f64)]) -> Vec<Vec<f32>> {
+        let mut samples = Vec::new();
+        for &(seconds, level) in sections {
+            let amplitude = 10f64.powf(level / 20.0);
+            let start = samples.len();
+            samples.extend((0..(seconds * SAMPLE_RATE as f64) as usize).map(|n| {
+                let t = (start + n) as f64 / SAMPLE_RATE as f64;
+                (amplitude * (2.0 * std::f64::consts::PI * 1000.0 * t).sin()) as f32
+            }));
+        }
+        vec![samples.clone(), samples]
+    }
+
+    fn assert_lufs(actual: Option<f64>, expected: f64) {
+        let actual = actual.expect("integrated loudness");
+        assert!(
+            (actual - expected).abs() <= 0.1,
+            "{} LUFS, expected {}",
+            actual,
+            expected
+        );
+    }
+
+    // EBU Tech 3341, test cases 1 to 3
+
+    #[test]
+    fn sine_at_minus_23_dbfs_reads_minus_23_lufs() {
+        let analysis = measure(&stereo_sine(&[(20.0, -23.0)]), SAMPLE_RATE).unwrap();
+        assert_lufs(analysis.integrated, -23.0);
+        assert!((analysis.true_peak.unwrap() + 23.0).abs() < 0.2);
+    }
+
+    #[test]
+    fn sine_at_minus_33_dbfs_reads_minus_33_lufs() {
+        let analysis = measure(&stereo_sine(&[(20.0, -33.0)]), SAMPLE_RATE).unwrap();
+        assert_lufs(analysis.integrated, -33.0);
+    }
+
+    #[test]
+    fn relative_gate_drops_the_quiet_sections() {
+        let sine = stereo_sine(&[(10.0, -36.0), (60.0, -23.0), (10.0, -36.0)]);
+        let analysis = measure(&sine, SAMPLE_RATE).unwrap();
+        assert_lufs(analysis.integrated, -23.0);
+    }
+
+    #[test]
+    fn digital_silence_has_no_loudness() {
+        let analysis = measure(&vec![vec![0.0; 5 * SAMPLE_RATE as usize]; 2], SAMPLE_RATE).unwrap();
+        assert_eq!(analysis.integrated, None);
+        assert_eq!(analysis.true_peak, None);
+        assert!(analysis.momentary.iter().all(|&lufs| lufs == -70.0));
+    }
+}
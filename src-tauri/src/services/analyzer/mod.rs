@@ -0,0 +1,47 @@
+pub mod chroma;
+pub mod features;
+pub mod loudness;
+pub mod peaks;
+pub mod pitch;
+pub mod rhythm;
+pub mod spectrogram;
+pub mod spectrum;
+pub mod window;
+
+/// Sample index at which each analysis frame ends.
+///
+/// With a frame rate the frames line up with video frames (like an
+/// `AnalyserNode` read once per rendered frame); otherwise they advance by `hop`.
+pub fn frame_ends(
+    total_frames: usize,
+    sample_rate: u32,
+    hop: usize,
+    frame_rate: Option<f64>,
+) -> Vec<usize> {
+    match frame_rate {
+        Some(fps) if fps > 0.0 => {
+            let duration = total_frames as f64 / sample_rate as f64;
+            let count = (duration * fps).ceil() as usize;
+            (0..count)
+                .map(|i| ((i as f64 / fps) * sample_rate as f64).round() as usize)
+                .collect()
+        }
+        _ => {
+            let hop = hop.max(1);
+            (0..total_frames.div_ceil(hop)).map(|i| i * hop).collect()
+        }
+    }
+}
+
+/// Copy `size` samples ending at `end` into `out`, zero-padding outside the signal.
+pub fn fill_frame(samples: &[f32], end: usize, size: usize, out: &mut [f32]) {
+    let start = end as isize - size as isize;
+    for (i, slot) in out.iter_mut().enumerate().take(size) {
+        let index = start + i as isize;
+        *slot = if index >= 0 && (index as usize) < samples.len() {
+            samples[index as usize]
+        } else {
+            0.0
+        };
+    }
+}
@@ -0,0 +1,337 @@
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{AppError, AppResult};
+
+/// Samples per bucket in the finest pyramid level. Zoom levels finer than
+/// this are answered straight from the samples.
+const BASE_BUCKET: usize = 256;
+
+/// One pyramid level: min, max and sum of squares per fixed-size bucket.
+struct PeakLevel {
+    bucket: usize,
+    min: Vec<f32>,
+    max: Vec<f32>,
+    sum_sq: Vec<f64>,
+}
+
+/// Multi-resolution min/max/energy summary of one signal. Level `n` has
+/// buckets of `BASE_BUCKET << n` samples, so any span of samples is covered
+/// exactly by at most two buckets per level plus the samples at its edges.
+pub struct PeakPyramid {
+    frames: usize,
+    levels: Vec<PeakLevel>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeaksRequest {
+    /// Output buckets, usually the width of the view in pixels.
+    pub buckets: usize,
+    /// Start of the view in seconds.
+    #[serde(default)]
+    pub start: f64,
+    /// End of the view in seconds (defaults to the end of the file).
+    pub end: Option<f64>,
+    /// Channel index; the mixdown when omitted.
+    pub channel: Option<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WaveformPeaks {
+    pub start: f64,
+    pub end: f64,
+    pub samples_per_bucket: f64,
+    pub min: Vec<f32>,
+    pub max: Vec<f32>,
+    pub rms: Vec<f32>,
+}
+
+/// Running min/max/energy over a span of samples or buckets.
+#[derive(Clone, Copy)]
+struct Accumulator {
+    min: f32,
+    max: f32,
+    sum_sq: f64,
+    count: usize,
+}
+
+impl Accumulator {
+    const EMPTY: Accumulator = Accumulator {
+        min: f32::INFINITY,
+        max: f32::NEG_INFINITY,
+        sum_sq: 0.0,
+        count: 0,
+    };
+
+    fn add_sample(&mut self, sample: f32) {
+        self.min = self.min.min(sample);
+        self.max = self.max.max(sample);
+        self.sum_sq += f64::from(sample) * f64::from(sample);
+        self.count += 1;
+    }
+
+    fn add_bucket(&mut self, min: f32, max: f32, sum_sq: f64, count: usize) {
+        self.min = self.min.min(min);
+        self.max = self.max.max(max);
+        self.sum_sq += sum_sq;
+        self.count += count;
+    }
+
+    /// `(min, max, rms)`, all zero for an empty span (past the end of the file).
+    fn finish(self) -> (f32, f32, f32) {
+        if self.count == 0 {
+            return (0.0, 0.0, 0.0);
+        }
+        (
+            self.min,
+            self.max,
+            (self.sum_sq / self.count as f64).sqrt() as f32,
+        )
+    }
+}
+
+impl PeakPyramid {
+    pub fn build(samples: &[f32]) -> Self {
+        let mut levels = Vec::new();
+
+        let mut level = PeakLevel {
+            bucket: BASE_BUCKET,
+            min: Vec::with_capacity(samples.len().div_ceil(BASE_BUCKET)),
+            max: Vec::with_capacity(samples.len().div_ceil(BASE_BUCKET)),
+            sum_sq: Vec::with_capacity(samples.len().div_ceil(BASE_BUCKET)),
+        };
+        for chunk in samples.chunks(BASE_BUCKET) {
+            let mut acc = Accumulator::EMPTY;
+            chunk.iter().for_each(|&s| acc.add_sample(s));
+            level.min.push(acc.min);
+            level.max.push(acc.max);
+            level.sum_sq.push(acc.sum_sq);
+        }
+
+        // Halve the resolution until a single bucket covers the whole signal
+        while level.min.len() > 1 {
+            let next = PeakLevel {
+                bucket: level.bucket * 2,
+                min: level
+                    .min
+                    .chunks(2)
+                    .map(|p| p.iter().copied().fold(f32::INFINITY, f32::min))
+                    .collect(),
+                max: level
+                    .max
+                    .chunks(2)
+                    .map(|p| p.iter().copied().fold(f32::NEG_INFINITY, f32::max))
+                    .collect(),
+                sum_sq: level.sum_sq.chunks(2).map(|p| p.iter().sum()).collect(),
+            };
+            levels.push(level);
+            level = next;
+        }
+        levels.push(level);
+
+        PeakPyramid {
+            frames: samples.len(),
+            levels,
+        }
+    }
+
+    /// Min/max/energy of exactly `samples[from..to]`, `to <= frames`.
+    /// Works up the levels like a segment tree: the samples before the first
+    /// and after the last whole `BASE_BUCKET` are read directly, then each
+    /// level contributes the bucket at either end that the next, coarser
+    /// level does not line up with.
+    fn accumulate(&self, samples: &[f32], mut from: usize, mut to: usize) -> Accumulator {
+        let mut acc = Accumulator::EMPTY;
+        if from >= to {
+            return acc;
+        }
+        let head = from.next_multiple_of(BASE_BUCKET).min(to);
+        samples[from..head].iter().for_each(|&s| acc.add_sample(s));
+        from = head;
+        if from == to {
+            return acc;
+        }
+        if to == self.frames {
+            // The last bucket of every level stops at the end of the signal
+            to = to.next_multiple_of(BASE_BUCKET);
+        } else {
+            let tail = (to - to % BASE_BUCKET).max(from);
+            samples[tail..to].iter().for_each(|&s| acc.add_sample(s));
+            to = tail;
+        }
+
+        for level in &self.levels {
+            if from >= to {
+                break;
+            }
+            let bucket = level.bucket;
+            let mut add = |b: usize| {
+                let count = ((b + 1) * bucket).min(self.frames) - b * bucket;
+                acc.add_bucket(level.min[b], level.max[b], level.sum_sq[b], count);
+            };
+            if !from.is_multiple_of(2 * bucket) {
+                add(from / bucket);
+                from += bucket;
+            }
+            if from < to && !to.is_multiple_of(2 * bucket) {
+                add(to / bucket - 1);
+                to -= bucket;
+            }
+        }
+        acc
+    }
+
+    /// Min/max/RMS for `buckets` equal spans of `[start, end)` (in samples).
+    /// `samples` must be the signal the pyramid was built from; the ragged
+    /// edges of each span are read from it.
+    pub fn query(
+        &self,
+        samples: &[f32],
+        start: f64,
+        end: f64,
+        buckets: usize,
+    ) -> (Vec<f32>, Vec<f32>, Vec<f32>) {
+        let span = (end - start) / buckets as f64;
+        let mut min = Vec::with_capacity(buckets);
+        let mut max = Vec::with_capacity(buckets);
+        let mut rms = Vec::with_capacity(buckets);
+        for i in 0..buckets {
+            let from = (start + span * i as f64).max(0.0) as usize;
+            let to = ((start + span * (i + 1) as f64).max(0.0) as usize)
+                .max(from + 1)
+                .min(self.frames);
+            let (lo, hi, r) = self.accumulate(samples, from, to).finish();
+            min.push(lo);
+            max.push(hi);
+            rms.push(r);
+        }
+        (min, max, rms)
+    }
+}
+
+/// Answer a peaks request for a signal and its pyramid.
+pub fn compute(
+    pyramid: &PeakPyramid,
+    samples: &[f32],
+    sample_rate: u32,
+    request: &PeaksRequest,
+) -> AppResult<WaveformPeaks> {
+    if request.buckets == 0 || request.buckets > 1 << 20 {
+        return Err(AppError::InvalidArgument(format!(
+            "buckets must be between 1 and {}, got {}",
+            1 << 20,
+            request.buckets
+        )));
+    }
+    let duration = pyramid.frames as f64 / sample_rate as f64;
+    let end = request.end.unwrap_or(duration);
+    if !request.start.is_finite() || !end.is_finite() || end <= request.start {
+        return Err(AppError::InvalidArgument(
+            "end must be after start".to_string(),
+        ));
+    }
+
+    let rate = sample_rate as f64;
+    let (min, max, rms) = pyramid.query(samples, request.start * rate, end * rate, request.buckets);
+    Ok(WaveformPeaks {
+        start: request.start,
+        end,
+        samples_per_bucket: (end - request.start) * rate / request.buckets as f64,
+        min,
+        max,
+        rms,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic noise with a slow envelope, not a multiple of any bucket size.
+    fn signal() -> Vec<f32> {
+        let mut state = 0x2545_f491_u32;
+        (0..100_003)
+            .map(|i| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let noise = (state >> 8) as f32 / (1 << 24) as f32 * 2.0 - 1.0;
+                noise * (i as f32 / 7919.0).sin()
+            })
+            .collect()
+    }
+
+    fn brute_force(samples: &[f32], start: f64, end: f64, buckets: usize) -> Vec<(f32, f32, f32)> {
+        let span = (end - start) / buckets as f64;
+        (0..buckets)
+            .map(|i| {
+                let from = (start + span * i as f64).max(0.0) as usize;
+                let to = ((start + span * (i + 1) as f64).max(0.0) as usize)
+                    .max(from + 1)
+                    .min(samples.len());
+                let window = samples.get(from..to).unwrap_or_default();
+                if window.is_empty() {
+                    return (0.0, 0.0, 0.0);
+                }
+                let min = window.iter().copied().fold(f32::INFINITY, f32::min);
+                let max = window.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                let sum_sq: f64 = window.iter().map(|&s| f64::from(s) * f64::from(s)).sum();
+                (min, max, (sum_sq / window.len() as f64).sqrt() as f32)
+            })
+            .collect()
+    }
+
+    fn assert_matches_brute_force(
+        samples: &[f32],
+        pyramid: &PeakPyramid,
+        start: f64,
+        end: f64,
+        buckets: usize,
+    ) {
+        let (min, max, rms) = pyramid.query(samples, start, end, buckets);
+        let expected = brute_force(samples, start, end, buckets);
+        for (i, &(lo, hi, r)) in expected.iter().enumerate() {
+            let at = format!("bucket {} of {} over {}..{}", i, buckets, start, end);
+            assert_eq!(min[i], lo, "min, {}", at);
+            assert_eq!(max[i], hi, "max, {}", at);
+            assert!((rms[i] - r).abs() <= 1e-5 * r.max(1e-3), "rms, {}", at);
+        }
+    }
+
+    #[test]
+    fn whole_signal_matches_brute_force_at_every_zoom() {
+        let samples = signal();
+        let pyramid = PeakPyramid::build(&samples);
+        let frames = samples.len() as f64;
+        // Spans from below the finest level to the whole signal, many of
+        // them between two levels (e.g. 333 and 770 samples per bucket)
+        for buckets in [1, 3, 7, 64, 130, 300, 391, 1000, 4096, 33_335, 100_003] {
+            assert_matches_brute_force(&samples, &pyramid, 0.0, frames, buckets);
+        }
+    }
+
+    #[test]
+    fn zoomed_views_match_brute_force() {
+        let samples = signal();
+        let pyramid = PeakPyramid::build(&samples);
+        for (start, end) in [
+            (0.0, 512.0),
+            (255.0, 257.0),
+            (1000.5, 9000.25),
+            (12_345.0, 98_765.0),
+            (65_536.0, 100_003.0),
+            (99_000.0, 120_000.0),
+        ] {
+            for buckets in [1, 5, 37, 800] {
+                assert_matches_brute_force(&samples, &pyramid, start, end, buckets);
+            }
+        }
+    }
+
+    #[test]
+    fn view_past_the_end_is_silent() {
+        let samples = signal();
+        let pyramid = PeakPyramid::build(&samples);
+        let (min, max, rms) = pyramid.query(&samples, 200_000.0, 300_000.0, 4);
+        assert!(min.iter().chain(&max).chain(&rms).all(|&v| v == 0.0));
+    }
+}
@@ -0,0 +1,253 @@
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use serde::{Deserialize, Serialize};
+
+use super::fill_frame;
+use crate::errors::{AppError, AppResult};
+
+/// Frames quieter than this RMS (-60 dBFS) are unvoiced without looking for a pitch.
+const SILENCE_RMS: f32 = 0.001;
+/// Number of YIN thresholds the voicing probability is spread over (pYIN).
+const THRESHOLDS: usize = 100;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PitchOptions {
+    pub hop_size: usize,
+    /// Lowest and highest f0 searched for, in Hz.
+    pub min_frequency: f32,
+    pub max_frequency: f32,
+    /// Frames whose voicing confidence is below this (0–1) have no f0.
+    pub voicing_threshold: f32,
+}
+
+impl Default for PitchOptions {
+    fn default() -> Self {
+        PitchOptions {
+            hop_size: 512,
+            min_frequency: 60.0,
+            max_frequency: 1000.0,
+            voicing_threshold: 0.5,
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PitchTrack {
+    pub sample_rate: u32,
+    pub hop_size: usize,
+    pub frame_count: usize,
+    /// Frame `i` is centred at `i * frame_duration` seconds.
+    pub frame_duration: f64,
+    /// Fundamental frequency in Hz, `None` where the frame is unvoiced.
+    pub f0: Vec<Option<f32>>,
+    /// Probability that the frame is voiced, 0–1.
+    pub confidence: Vec<f32>,
+}
+
+/// Weights of the YIN thresholds 0.005, 0.015, ... 0.995 under the Beta(2, 18)
+/// prior of pYIN (mean 0.1, the classic YIN threshold).
+fn threshold_prior() -> Vec<(f32, f32)> {
+    let thresholds: Vec<(f32, f32)> = (0..THRESHOLDS)
+        .map(|k| {
+            let s = (k as f32 + 0.5) / THRESHOLDS as f32;
+            (s, s * (1.0 - s).powi(17))
+        })
+        .collect();
+    let total: f32 = thresholds.iter().map(|(_, w)| w).sum();
+    thresholds
+        .into_iter()
+        .map(|(s, w)| (s, w / total))
+        .collect()
+}
+
+/// Most probable period (in samples, interpolated) of one frame's cumulative
+/// mean normalized difference `cmnd` and the probability that it is voiced.
+/// Every threshold picks the first dip below it, as YIN does with a single
+/// one, and the dip collects that threshold's prior weight.
+fn best_period(cmnd: &[f32], tau_min: usize, prior: &[(f32, f32)]) -> Option<(f32, f32)> {
+    let dips: Vec<usize> = (tau_min.max(1)..cmnd.len() - 1)
+        .filter(|&tau| cmnd[tau] < cmnd[tau - 1] && cmnd[tau] <= cmnd[tau + 1])
+        .collect();
+    let mut mass = vec![0.0f32; dips.len()];
+    for &(threshold, weight) in prior {
+        if let Some(index) = dips.iter().position(|&tau| cmnd[tau] < threshold) {
+            mass[index] += weight;
+        }
+    }
+    let (index, _) = mass.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1))?;
+    let voiced: f32 = mass.iter().sum();
+    if voiced <= 0.0 {
+        return None;
+    }
+
+    // Parabolic interpolation around the dip
+    let tau = dips[index];
+    let (a, b, c) = (cmnd[tau - 1], cmnd[tau], cmnd[tau + 1]);
+    let denominator = a - 2.0 * b + c;
+    let offset = if denominator.abs() > f32::EPSILON {
+        (0.5 * (a - c) / denominator).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+    Some((tau as f32 + offset, voiced.min(1.0)))
+}
+
+/// f0 curve and voicing confidence of a monophonic signal (a voice, a solo
+/// instrument) with a probabilistic YIN: the difference function is computed
+/// with an FFT, and instead of one fixed threshold the voicing probability
+/// is the share of pYIN's threshold prior under which a period is found.
+pub fn analyze(samples: &[f32], sample_rate: u32, options: &PitchOptions) -> AppResult<PitchTrack> {
+    if options.hop_size == 0 {
+        return Err(AppError::InvalidArgument(
+            "hopSize must be positive".to_string(),
+        ));
+    }
+    let nyquist_quarter = sample_rate as f32 / 4.0;
+    if !(20.0..nyquist_quarter).contains(&options.min_frequency)
+        || !(options.min_frequency..=nyquist_quarter).contains(&options.max_frequency)
+        || options.max_frequency <= options.min_frequency
+    {
+        return Err(AppError::InvalidArgument(format!(
+            "Frequency range must satisfy 20 <= minFrequency < maxFrequency <= {}, got {}-{}",
+            nyquist_quarter, options.min_frequency, options.max_frequency
+        )));
+    }
+    if !(0.0..=1.0).contains(&options.voicing_threshold) {
+        return Err(AppError::InvalidArgument(format!(
+            "voicingThreshold must be between 0 and 1, got {}",
+            options.voicing_threshold
+        )));
+    }
+
+    let tau_min = (sample_rate as f32 / options.max_frequency).floor() as usize;
+    let tau_max = (sample_rate as f32 / options.min_frequency).ceil() as usize;
+    // The first half of the frame is compared with itself shifted by up to tau_max + 1
+    let size = (2 * (tau_max + 2)).next_power_of_two();
+    let half = size / 2;
+
+    let mut planner = FftPlanner::<f32>::new();
+    let forward = planner.plan_fft_forward(size);
+    let inverse = planner.plan_fft_inverse(size);
+    let prior = threshold_prior();
+    let mut frame = vec![0.0f32; size];
+    let mut signal = vec![Complex::new(0.0f32, 0.0); size];
+    let mut head = vec![Complex::new(0.0f32, 0.0); size];
+    let mut energy = vec![0.0f32; size + 1];
+    let mut cmnd = vec![0.0f32; tau_max + 2];
+
+    let frame_count = samples.len().div_ceil(options.hop_size);
+    let mut f0 = Vec::with_capacity(frame_count);
+    let mut confidence = Vec::with_capacity(frame_count);
+    for i in 0..frame_count {
+        fill_frame(samples, i * options.hop_size + half, size, &mut frame);
+        // Prefix sums of x² give the energy of any stretch of the frame
+        for (j, sample) in frame.iter().enumerate() {
+            energy[j + 1] = energy[j] + sample * sample;
+        }
+        if (energy[half] / half as f32).sqrt() < SILENCE_RMS {
+            f0.push(None);
+            confidence.push(0.0);
+            continue;
+        }
+
+        // r(tau) = Σ x[j] x[j + tau] for j < half, as one circular correlation
+        for (j, sample) in frame.iter().enumerate() {
+            signal[j] = Complex::new(*sample, 0.0);
+            head[j] = Complex::new(if j < half { *sample } else { 0.0 }, 0.0);
+        }
+        forward.process(&mut signal);
+        forward.process(&mut head);
+        for (s, h) in signal.iter_mut().zip(&head) {
+            *s *= h.conj();
+        }
+        inverse.process(&mut signal);
+
+        // d(tau) = e(0..half) + e(tau..tau + half) - 2 r(tau), normalized by its running mean
+        cmnd[0] = 1.0;
+        let mut running = 0.0f32;
+        for (tau, value) in cmnd.iter_mut().enumerate().skip(1) {
+            let correlation = signal[tau].re / size as f32;
+            let difference =
+                (energy[half] + energy[tau + half] - energy[tau] - 2.0 * correlation).max(0.0);
+            running += difference;
+            *value = if running > 0.0 {
+                difference * tau as f32 / running
+            } else {
+                1.0
+            };
+        }
+
+        match best_period(&cmnd, tau_min, &prior) {
+            Some((period, voiced)) => {
+                f0.push((voiced >= options.voicing_threshold).then(|| sample_rate as f32 / period));
+                confidence.push(voiced);
+            }
+            None => {
+                f0.push(None);
+                confidence.push(0.0);
+            }
+        }
+    }
+
+    Ok(PitchTrack {
+        sample_rate,
+        hop_size: options.hop_size,
+        frame_count,
+        frame_duration: options.hop_size as f64 / sample_rate as f64,
+        f0,
+        confidence,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 44_100;
+
+    fn sine(hz: f32, seconds: f32) -> Vec<f32> {
+        (0..(seconds * SAMPLE_RATE as f32) as usize)
+            .map(|n| 0.5 * (2.0 * std::f32::consts::PI * hz * n as f32 / SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn sine_at_220_hz_is_voiced_at_220_hz() {
+        let track = analyze(&sine(220.0, 1.0), SAMPLE_RATE, &PitchOptions::default()).unwrap();
+        // Frames away from the edges, where the window is full of signal
+        let inner = 4..track.frame_count - 4;
+        for i in inner {
+            let f0 = track.f0[i].unwrap_or_else(|| panic!("frame {} is unvoiced", i));
+            assert!((f0 - 220.0).abs() < 1.0, "frame {}: {} Hz", i, f0);
+            assert!(
+                track.confidence[i] > 0.9,
+                "frame {}: {}",
+                i,
+                track.confidence[i]
+            );
+        }
+    }
+
+    #[test]
+    fn octave_apart_sines_are_not_confused() {
+        for hz in [110.0, 440.0] {
+            let track = analyze(&sine(hz, 0.5), SAMPLE_RATE, &PitchOptions::default()).unwrap();
+            let middle = track.f0[track.frame_count / 2].unwrap();
+            assert!(
+                (middle - hz).abs() < hz * 0.01,
+                "{} Hz read as {}",
+                hz,
+                middle
+            );
+        }
+    }
+
+    #[test]
+    fn silence_is_unvoiced() {
+        let track = analyze(&[0.0; 22_050], SAMPLE_RATE, &PitchOptions::default()).unwrap();
+        assert!(track.f0.iter().all(Option::is_none));
+        assert!(track.confidence.iter().all(|&c| c == 0.0));
+    }
+}
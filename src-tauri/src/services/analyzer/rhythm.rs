@@ -0,0 +1,447 @@
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use serde::{Deserialize, Serialize};
+
+use super::fill_frame;
+use super::window::WindowFunction;
+use crate::errors::{AppError, AppResult};
+
+const FRAME_SIZE: usize = 2048;
+const HOP_SIZE: usize = 512;
+/// Upper edge of the band whose flux is used to find downbeats (kick and bass).
+const LOW_BAND_HZ: f32 = 150.0;
+/// How strongly beat tracking sticks to the local tempo (Ellis 2007).
+const TIGHTNESS: f64 = 100.0;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RhythmOptions {
+    pub min_bpm: f64,
+    pub max_bpm: f64,
+    /// Beats per bar used to pick downbeats.
+    pub beats_per_bar: usize,
+    /// Seconds of audio behind each point of the tempo curve.
+    pub tempo_window: f64,
+}
+
+impl Default for RhythmOptions {
+    fn default() -> Self {
+        RhythmOptions {
+            min_bpm: 60.0,
+            max_bpm: 200.0,
+            beats_per_bar: 4,
+            tempo_window: 8.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TempoPoint {
+    pub time: f64,
+    pub bpm: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RhythmAnalysis {
+    /// Tempo of the whole file; 0 when no pulse was found.
+    pub bpm: f64,
+    /// Seconds of every detected onset.
+    pub onsets: Vec<f64>,
+    pub beats: Vec<f64>,
+    /// The beats that start a bar (a subset of `beats`).
+    pub downbeats: Vec<f64>,
+    pub beats_per_bar: usize,
+    /// Local tempo, one point per second of audio.
+    pub tempo_curve: Vec<TempoPoint>,
+}
+
+/// Positive change of log magnitude per STFT frame (spectral flux), over the
+/// whole spectrum and over the low band only.
+fn onset_envelopes(samples: &[f32], sample_rate: u32) -> (Vec<f32>, Vec<f32>) {
+    let fft_bins = FRAME_SIZE / 2 + 1;
+    let low_bins =
+        ((LOW_BAND_HZ * FRAME_SIZE as f32 / sample_rate as f32).ceil() as usize).clamp(1, fft_bins);
+    let window = WindowFunction::Hann.coefficients(FRAME_SIZE);
+    let gain = 2.0 / window.iter().sum::<f32>();
+    let fft = FftPlanner::<f32>::new().plan_fft_forward(FRAME_SIZE);
+    let mut frame = vec![0.0f32; FRAME_SIZE];
+    let mut buffer = vec![Complex::new(0.0f32, 0.0); FRAME_SIZE];
+    let mut previous = vec![0.0f32; fft_bins];
+    let mut current = vec![0.0f32; fft_bins];
+
+    let frame_count = samples.len().div_ceil(HOP_SIZE);
+    let mut flux = Vec::with_capacity(frame_count);
+    let mut low_flux = Vec::with_capacity(frame_count);
+    for i in 0..frame_count {
+        fill_frame(
+            samples,
+            i * HOP_SIZE + FRAME_SIZE / 2,
+            FRAME_SIZE,
+            &mut frame,
+        );
+        for ((slot, sample), w) in buffer.iter_mut().zip(&frame).zip(&window) {
+            *slot = Complex::new(sample * w, 0.0);
+        }
+        fft.process(&mut buffer);
+        for (level, value) in current.iter_mut().zip(&buffer) {
+            // Log compression so quiet instruments still register
+            *level = (1.0 + 100.0 * value.norm() * gain).ln();
+        }
+        let rise = |k: usize| {
+            if i == 0 {
+                0.0
+            } else {
+                (current[k] - previous[k]).max(0.0)
+            }
+        };
+        flux.push((0..fft_bins).map(rise).sum());
+        low_flux.push((0..low_bins).map(rise).sum());
+        std::mem::swap(&mut previous, &mut current);
+    }
+    (flux, low_flux)
+}
+
+fn normalize(values: &mut [f32]) {
+    let max = values.iter().copied().fold(0.0f32, f32::max);
+    if max > 0.0 {
+        values.iter_mut().for_each(|v| *v /= max);
+    }
+}
+
+fn mean(values: &[f32]) -> f32 {
+    if values.is_empty() {
+        0.0
+    } else {
+        values.iter().sum::<f32>() / values.len() as f32
+    }
+}
+
+/// Local maxima that stand out from their surroundings, at least `wait` frames apart.
+fn pick_peaks(envelope: &[f32], wait: usize) -> Vec<usize> {
+    const MAX_RADIUS: usize = 3;
+    const MEAN_RADIUS: usize = 10;
+    const DELTA: f32 = 0.05;
+
+    let mut peaks: Vec<usize> = Vec::new();
+    for (n, &value) in envelope.iter().enumerate() {
+        let around = |radius: usize| {
+            &envelope[n.saturating_sub(radius)..(n + radius + 1).min(envelope.len())]
+        };
+        let is_max = around(MAX_RADIUS).iter().all(|&v| v <= value);
+        if is_max
+            && value >= mean(around(MEAN_RADIUS)) + DELTA
+            && peaks.last().is_none_or(|&last| n - last >= wait)
+        {
+            peaks.push(n);
+        }
+    }
+    peaks
+}
+
+/// Beat period in frames with the strongest autocorrelation between
+/// `min_lag` and `max_lag`, weighted towards 120 BPM.
+fn estimate_period(
+    envelope: &[f32],
+    min_lag: usize,
+    max_lag: usize,
+    frame_duration: f64,
+) -> Option<f64> {
+    let centre = mean(envelope);
+    let centred: Vec<f32> = envelope.iter().map(|v| v - centre).collect();
+    let max_lag = max_lag.min(centred.len().saturating_sub(1));
+    if min_lag < 1 || min_lag + 1 >= max_lag {
+        return None;
+    }
+
+    let correlation: Vec<f64> = (min_lag - 1..=max_lag + 1)
+        .map(|lag| {
+            let raw: f64 = centred
+                .iter()
+                .zip(&centred[lag.min(centred.len())..])
+                .map(|(a, b)| (a * b) as f64)
+                .sum();
+            let bpm = 60.0 / (lag as f64 * frame_duration);
+            // Log-normal prior, one octave wide
+            raw * (-0.5 * (bpm / 120.0).log2().powi(2)).exp()
+        })
+        .collect();
+    let (best, &peak) = correlation[1..correlation.len() - 1]
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))?;
+    if peak <= 0.0 {
+        return None;
+    }
+
+    // Parabolic interpolation around the best lag
+    let (left, right) = (correlation[best], correlation[best + 2]);
+    let curvature = left - 2.0 * peak + right;
+    let offset = if curvature < 0.0 {
+        0.5 * (left - right) / curvature
+    } else {
+        0.0
+    };
+    Some((min_lag + best) as f64 + offset.clamp(-0.5, 0.5))
+}
+
+/// Dynamic-programming beat tracker: the best chain of strong onsets spaced
+/// by the local period `period_at(frame)`.
+fn track_beats(envelope: &[f32], period_at: impl Fn(usize) -> f64) -> Vec<usize> {
+    let deviation = {
+        let centre = mean(envelope);
+        (envelope.iter().map(|v| (v - centre).powi(2)).sum::<f32>() / envelope.len().max(1) as f32)
+            .sqrt()
+    };
+    if deviation <= 0.0 {
+        return Vec::new();
+    }
+    let strength: Vec<f64> = envelope.iter().map(|v| (*v / deviation) as f64).collect();
+
+    let mut score = vec![0.0f64; strength.len()];
+    let mut backlink: Vec<Option<usize>> = vec![None; strength.len()];
+    for t in 0..strength.len() {
+        let period = period_at(t);
+        let earliest = t as f64 - 2.0 * period;
+        let latest = t as f64 - period / 2.0;
+        let mut best: Option<(usize, f64)> = None;
+        if latest >= 0.0 {
+            let (first, last) = (
+                earliest.max(0.0).round() as usize,
+                (latest.round() as usize).min(t - 1),
+            );
+            for (p, &previous) in score.iter().enumerate().take(last + 1).skip(first) {
+                let interval = (t - p) as f64;
+                let candidate = previous - TIGHTNESS * (interval / period).ln().powi(2);
+                if best.is_none_or(|(_, value)| candidate > value) {
+                    best = Some((p, candidate));
+                }
+            }
+        }
+        score[t] = strength[t] + best.map_or(0.0, |(_, value)| value);
+        backlink[t] = best.map(|(p, _)| p);
+    }
+
+    // Best-scoring frame within the last period, then follow the chain back
+    let last_period = period_at(strength.len() - 1).round() as usize;
+    let tail = strength.len().saturating_sub(last_period.max(1));
+    let Some(mut t) = (tail..strength.len()).max_by(|a, b| score[*a].total_cmp(&score[*b])) else {
+        return Vec::new();
+    };
+    let mut beats = vec![t];
+    while let Some(previous) = backlink[t] {
+        beats.push(previous);
+        t = previous;
+    }
+    beats.reverse();
+    beats
+}
+
+/// Spectral-flux onsets, a dynamic-programming beat track following the local
+/// tempo, and downbeats every `beats_per_bar` beats, phased on the bass accents.
+pub fn analyze(
+    samples: &[f32],
+    sample_rate: u32,
+    options: &RhythmOptions,
+) -> AppResult<RhythmAnalysis> {
+    if !(options.min_bpm > 0.0 && options.min_bpm < options.max_bpm && options.max_bpm <= 400.0) {
+        return Err(AppError::InvalidArgument(format!(
+            "Invalid BPM range {}–{}",
+            options.min_bpm, options.max_bpm
+        )));
+    }
+    if !(1..=16).contains(&options.beats_per_bar) {
+        return Err(AppError::InvalidArgument(format!(
+            "beatsPerBar must be between 1 and 16, got {}",
+            options.beats_per_bar
+        )));
+    }
+    if !options.tempo_window.is_finite() || options.tempo_window <= 0.0 {
+        return Err(AppError::InvalidArgument(
+            "tempoWindow must be positive".to_string(),
+        ));
+    }
+    if sample_rate == 0 {
+        return Err(AppError::InvalidArgument(
+            "sampleRate must be positive".to_string(),
+        ));
+    }
+
+    let frame_duration = HOP_SIZE as f64 / sample_rate as f64;
+    let time = |frame: usize| frame as f64 * frame_duration;
+    let mut result = RhythmAnalysis {
+        bpm: 0.0,
+        onsets: Vec::new(),
+        beats: Vec::new(),
+        downbeats: Vec::new(),
+        beats_per_bar: options.beats_per_bar,
+        tempo_curve: Vec::new(),
+    };
+
+    let (mut envelope, mut low) = onset_envelopes(samples, sample_rate);
+    normalize(&mut envelope);
+    normalize(&mut low);
+    if envelope.iter().all(|v| *v == 0.0) {
+        return Ok(result);
+    }
+
+    // Onsets closer than 30 ms are one event
+    let wait = (0.03 / frame_duration).ceil() as usize;
+    let onsets = pick_peaks(&envelope, wait);
+    result.onsets = onsets.iter().map(|&n| time(n)).collect();
+
+    let min_lag = (60.0 / (options.max_bpm * frame_duration)).floor().max(1.0) as usize;
+    let max_lag = (60.0 / (options.min_bpm * frame_duration)).ceil() as usize;
+    let Some(global_period) = estimate_period(&envelope, min_lag, max_lag, frame_duration) else {
+        return Ok(result);
+    };
+    result.bpm = 60.0 / (global_period * frame_duration);
+
+    // Local tempo once per second, falling back to the global tempo where the window is silent
+    let half_window = ((options.tempo_window / 2.0) / frame_duration).round() as usize;
+    let step = (1.0 / frame_duration).round().max(1.0) as usize;
+    let mut local_periods: Vec<f64> = Vec::new();
+    for centre in (0..envelope.len()).step_by(step) {
+        let span = &envelope
+            [centre.saturating_sub(half_window)..(centre + half_window).min(envelope.len())];
+        let period = estimate_period(span, min_lag, max_lag, frame_duration)
+            // Keep octave jumps out of the curve: only accept periods near the global one
+            .filter(|p| (p / global_period).log2().abs() < 0.4)
+            .unwrap_or(global_period);
+        local_periods.push(period);
+        result.tempo_curve.push(TempoPoint {
+            time: time(centre),
+            bpm: 60.0 / (period * frame_duration),
+        });
+    }
+    let period_at = |frame: usize| local_periods[(frame / step).min(local_periods.len() - 1)];
+
+    // Drop beats in the silence before the first and after the last onset
+    let (first_onset, last_onset) = (onsets.first().copied(), onsets.last().copied());
+    let beats: Vec<usize> = track_beats(&envelope, period_at)
+        .into_iter()
+        .filter(|&beat| {
+            let margin = period_at(beat) / 2.0;
+            first_onset.is_some_and(|first| beat as f64 >= first as f64 - margin)
+                && last_onset.is_some_and(|last| beat as f64 <= last as f64 + margin)
+        })
+        .collect();
+
+    // The bar phase whose beats carry the most low-band onset energy
+    let accent = if low.iter().any(|v| *v > 0.0) {
+        &low
+    } else {
+        &envelope
+    };
+    let phase = (0..options.beats_per_bar.min(beats.len().max(1)))
+        .max_by(|a, b| {
+            let weight = |phase: usize| -> f32 {
+                let accents: Vec<f32> = beats
+                    .iter()
+                    .skip(phase)
+                    .step_by(options.beats_per_bar)
+                    .map(|&beat| accent[beat])
+                    .collect();
+                mean(&accents)
+            };
+            weight(*a).total_cmp(&weight(*b))
+        })
+        .unwrap_or(0);
+
+    result.downbeats = beats
+        .iter()
+        .skip(phase)
+        .step_by(options.beats_per_bar)
+        .map(|&beat| time(beat))
+        .collect();
+    result.beats = beats.into_iter().map(time).collect();
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 22_050;
+    /// Time of the first click.
+    const OFFSET: f64 = 0.5;
+
+    /// Decaying noise-like clicks at `bpm`, with a 60 Hz thump on every
+    /// fourth one for the downbeats.
+    fn click_track(bpm: f64, seconds: f64) -> Vec<f32> {
+        let rate = SAMPLE_RATE as f64;
+        let mut samples = vec![0.0f32; (seconds * rate) as usize];
+        let period = 60.0 / bpm;
+        let click = (0.05 * rate) as usize;
+        for k in 0.. {
+            let time = OFFSET + k as f64 * period;
+            if time >= seconds - 0.1 {
+                break;
+            }
+            let start = (time * rate) as usize;
+            for (i, sample) in samples[start..start + click].iter_mut().enumerate() {
+                let envelope = (-(i as f32) / (0.01 * rate as f32)).exp();
+                let high = (i as f32 * 0.37).sin() * 0.3;
+                let low = if k % 4 == 0 {
+                    (2.0 * std::f32::consts::PI * 60.0 * i as f32 / rate as f32).sin() * 0.8
+                } else {
+                    0.0
+                };
+                *sample += (high + low) * envelope;
+            }
+        }
+        samples
+    }
+
+    #[test]
+    fn click_track_recovers_tempo_and_beats() {
+        for bpm in [90.0, 120.0, 150.0] {
+            let analysis = analyze(
+                &click_track(bpm, 20.0),
+                SAMPLE_RATE,
+                &RhythmOptions::default(),
+            )
+            .unwrap();
+            assert!(
+                (analysis.bpm - bpm).abs() < 2.0,
+                "{} BPM read as {}",
+                bpm,
+                analysis.bpm
+            );
+
+            let period = 60.0 / bpm;
+            let clicks = ((20.0 - 0.1 - OFFSET) / period).ceil() as usize;
+            assert!(
+                analysis.beats.len() + 2 >= clicks,
+                "{} of {} beats",
+                analysis.beats.len(),
+                clicks
+            );
+            for beat in &analysis.beats {
+                let nearest = OFFSET + ((beat - OFFSET) / period).round() * period;
+                assert!(
+                    (beat - nearest).abs() < 0.04,
+                    "beat at {} s at {} BPM",
+                    beat,
+                    bpm
+                );
+            }
+            for downbeat in &analysis.downbeats {
+                let click = ((downbeat - OFFSET) / period).round() as i64;
+                assert_eq!(click % 4, 0, "downbeat at {} s at {} BPM", downbeat, bpm);
+            }
+            assert!(analysis
+                .tempo_curve
+                .iter()
+                .all(|point| (point.bpm - bpm).abs() < 3.0));
+        }
+    }
+
+    #[test]
+    fn silence_has_no_beats() {
+        let analysis = analyze(&[0.0; 44_100], 44_100, &RhythmOptions::default()).unwrap();
+        assert_eq!(analysis.bpm, 0.0);
+        assert!(analysis.beats.is_empty());
+    }
+}
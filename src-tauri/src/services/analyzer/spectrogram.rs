@@ -0,0 +1,371 @@
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use serde::{Deserialize, Serialize};
+
+use super::fill_frame;
+use super::window::WindowFunction;
+use crate::errors::{AppError, AppResult};
+
+/// Most output rows, and most cells (frames × rows) in one spectrogram.
+const MAX_BINS: usize = 4096;
+const MAX_CELLS: usize = 1 << 26;
+
+fn default_window() -> WindowFunction {
+    WindowFunction::Hann
+}
+
+fn default_db_range() -> f32 {
+    80.0
+}
+
+/// How output rows are spaced along the frequency axis.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FrequencyScale {
+    #[default]
+    Linear,
+    Log,
+    Mel,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpectrogramOptions {
+    pub window_size: usize,
+    pub hop_size: usize,
+    #[serde(default = "default_window")]
+    pub window: WindowFunction,
+    #[serde(default)]
+    pub scale: FrequencyScale,
+    /// Output rows; defaults to `window_size / 2` (128 for mel), at most 4096.
+    pub bins: Option<usize>,
+    /// Lowest frequency shown; defaults to 0 Hz (20 Hz for log).
+    pub min_freq: Option<f32>,
+    /// Highest frequency shown; defaults to Nyquist.
+    pub max_freq: Option<f32>,
+    /// Dynamic range below the loudest cell that maps onto 0–255.
+    #[serde(default = "default_db_range")]
+    pub db_range: f32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Spectrogram {
+    pub sample_rate: u32,
+    pub window_size: usize,
+    pub hop_size: usize,
+    pub frame_count: usize,
+    pub bin_count: usize,
+    /// Frame `i` is centred at `i * hop_size / sample_rate` seconds.
+    pub frame_duration: f64,
+    /// Centre frequency of each row in Hz, lowest first.
+    pub frequencies: Vec<f32>,
+    /// Level of the loudest cell in dBFS; 255 in `values`.
+    pub max_decibels: f32,
+    /// `frame_count * bin_count` values, frame-major with the lowest
+    /// frequency first: 0 is `max_decibels - db_range` or quieter. Sent as
+    /// raw bytes after the JSON header (see `to_bytes`), never as JSON.
+    #[serde(skip)]
+    pub values: Vec<u8>,
+}
+
+impl Spectrogram {
+    /// The IPC payload: the header length as a little-endian u32, the other
+    /// fields as a JSON header, then `values` as they are.
+    pub fn to_bytes(&self) -> AppResult<Vec<u8>> {
+        let header = serde_json::to_vec(self).map_err(|e| {
+            AppError::Internal(format!("Failed to serialize spectrogram header: {}", e))
+        })?;
+        let mut bytes = Vec::with_capacity(4 + header.len() + self.values.len());
+        bytes.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&header);
+        bytes.extend_from_slice(&self.values);
+        Ok(bytes)
+    }
+}
+
+fn hz_to_mel(hz: f32) -> f32 {
+    2595.0 * (1.0 + hz / 700.0).log10()
+}
+
+fn mel_to_hz(mel: f32) -> f32 {
+    700.0 * (10f32.powf(mel / 2595.0) - 1.0)
+}
+
+/// `rows + 2` band edges from `min` to `max`; row `k` peaks at edge `k + 1`.
+fn band_edges(scale: FrequencyScale, min: f32, max: f32, rows: usize) -> Vec<f32> {
+    let steps = (rows + 1) as f32;
+    (0..rows + 2)
+        .map(|i| {
+            let t = i as f32 / steps;
+            match scale {
+                FrequencyScale::Linear => min + (max - min) * t,
+                FrequencyScale::Log => min * (max / min).powf(t),
+                FrequencyScale::Mel => {
+                    mel_to_hz(hz_to_mel(min) + (hz_to_mel(max) - hz_to_mel(min)) * t)
+                }
+            }
+        })
+        .collect()
+}
+
+/// Triangular filter per row over the FFT bins, normalised to sum to 1 so
+/// each row is an average power. A filter narrower than one bin (low rows
+/// of a log scale) falls back to interpolating between the two nearest bins.
+fn filterbank(edges: &[f32], bin_hz: f32, fft_bins: usize) -> Vec<Vec<(usize, f32)>> {
+    edges
+        .windows(3)
+        .map(|band| {
+            let (lo, center, hi) = (band[0], band[1], band[2]);
+            let first = (lo / bin_hz).ceil().max(0.0) as usize;
+            let last = ((hi / bin_hz).floor() as usize).min(fft_bins - 1);
+            let mut weights: Vec<(usize, f32)> = (first..=last)
+                .filter_map(|k| {
+                    let f = k as f32 * bin_hz;
+                    let w = if f <= center {
+                        (f - lo) / (center - lo)
+                    } else {
+                        (hi - f) / (hi - center)
+                    };
+                    (w > 0.0).then_some((k, w))
+                })
+                .collect();
+
+            if weights.is_empty() {
+                let position = (center / bin_hz).min((fft_bins - 1) as f32);
+                let below = position.floor() as usize;
+                let above = (below + 1).min(fft_bins - 1);
+                let fraction = position - below as f32;
+                weights = vec![(below, 1.0 - fraction), (above, fraction)];
+            }
+            let total: f32 = weights.iter().map(|(_, w)| w).sum();
+            weights.iter_mut().for_each(|(_, w)| *w /= total);
+            weights
+        })
+        .collect()
+}
+
+pub fn compute(
+    samples: &[f32],
+    sample_rate: u32,
+    options: &SpectrogramOptions,
+) -> AppResult<Spectrogram> {
+    let size = options.window_size;
+    if !size.is_power_of_two() || !(32..=32768).contains(&size) {
+        return Err(AppError::InvalidArgument(format!(
+            "windowSize must be a power of two between 32 and 32768, got {}",
+            size
+        )));
+    }
+    if options.hop_size == 0 {
+        return Err(AppError::InvalidArgument(
+            "hopSize must be positive".to_string(),
+        ));
+    }
+    if !options.db_range.is_finite() || options.db_range <= 0.0 {
+        return Err(AppError::InvalidArgument(
+            "dbRange must be positive".to_string(),
+        ));
+    }
+
+    let nyquist = sample_rate as f32 / 2.0;
+    let min_freq = options.min_freq.unwrap_or(match options.scale {
+        FrequencyScale::Log => 20.0,
+        _ => 0.0,
+    });
+    let max_freq = options.max_freq.unwrap_or(nyquist).min(nyquist);
+    if min_freq < 0.0
+        || min_freq >= max_freq
+        || (options.scale == FrequencyScale::Log && min_freq <= 0.0)
+    {
+        return Err(AppError::InvalidArgument(format!(
+            "Invalid frequency range {}–{} Hz",
+            min_freq, max_freq
+        )));
+    }
+    let rows = options.bins.unwrap_or(match options.scale {
+        FrequencyScale::Mel => 128,
+        _ => (size / 2).min(MAX_BINS),
+    });
+    if !(1..=MAX_BINS).contains(&rows) {
+        return Err(AppError::InvalidArgument(format!(
+            "bins must be between 1 and {}, got {}",
+            MAX_BINS, rows
+        )));
+    }
+    let frame_count = samples.len().div_ceil(options.hop_size);
+    if frame_count.saturating_mul(rows) > MAX_CELLS {
+        return Err(AppError::InvalidArgument(format!(
+            "{} frames of {} bins is more than {} cells; raise hopSize or lower bins",
+            frame_count, rows, MAX_CELLS
+        )));
+    }
+
+    let fft_bins = size / 2 + 1;
+    let edges = band_edges(options.scale, min_freq, max_freq, rows);
+    let filters = filterbank(&edges, sample_rate as f32 / size as f32, fft_bins);
+
+    let window = options.window.coefficients(size);
+    // Scale so a full-scale sine reads 0 dBFS whatever the window
+    let gain = 2.0 / window.iter().sum::<f32>();
+    let fft = FftPlanner::<f32>::new().plan_fft_forward(size);
+    let mut frame = vec![0.0f32; size];
+    let mut buffer = vec![Complex::new(0.0f32, 0.0); size];
+    let mut power = vec![0.0f32; fft_bins];
+
+    let mut decibels = Vec::with_capacity(frame_count * rows);
+    for i in 0..frame_count {
+        // Centre the window on the frame time
+        fill_frame(samples, i * options.hop_size + size / 2, size, &mut frame);
+        for ((slot, sample), w) in buffer.iter_mut().zip(&frame).zip(&window) {
+            *slot = Complex::new(sample * w, 0.0);
+        }
+        fft.process(&mut buffer);
+        for (p, value) in power.iter_mut().zip(&buffer) {
+            *p = (value.norm() * gain).powi(2);
+        }
+        decibels.extend(filters.iter().map(|filter| {
+            let band: f32 = filter.iter().map(|&(k, w)| power[k] * w).sum();
+            10.0 * band.max(1e-20).log10()
+        }));
+    }
+
+    let max_decibels = decibels.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let floor = max_decibels - options.db_range;
+    let values = decibels
+        .iter()
+        .map(|db| (255.0 * (db - floor) / options.db_range).clamp(0.0, 255.0) as u8)
+        .collect();
+
+    Ok(Spectrogram {
+        sample_rate,
+        window_size: size,
+        hop_size: options.hop_size,
+        frame_count,
+        bin_count: rows,
+        frame_duration: options.hop_size as f64 / sample_rate as f64,
+        frequencies: edges[1..=rows].to_vec(),
+        max_decibels: if max_decibels.is_finite() {
+            max_decibels
+        } else {
+            -200.0
+        },
+        values,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(window_size: usize, hop_size: usize) -> SpectrogramOptions {
+        SpectrogramOptions {
+            window_size,
+            hop_size,
+            window: WindowFunction::Hann,
+            scale: FrequencyScale::Linear,
+            bins: None,
+            min_freq: None,
+            max_freq: None,
+            db_range: 80.0,
+        }
+    }
+
+    fn assert_close(actual: &[f32], expected: &[f32], tolerance: f32) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() <= tolerance, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn linear_edges_are_evenly_spaced() {
+        let edges = band_edges(FrequencyScale::Linear, 0.0, 1000.0, 3);
+        assert_close(&edges, &[0.0, 250.0, 500.0, 750.0, 1000.0], 1e-3);
+    }
+
+    #[test]
+    fn log_edges_are_evenly_spaced_in_octaves() {
+        let edges = band_edges(FrequencyScale::Log, 20.0, 20480.0, 9);
+        let octaves: Vec<f32> = (0..11).map(|i| 20.0 * 2f32.powi(i)).collect();
+        assert_close(&edges, &octaves, 0.05);
+    }
+
+    #[test]
+    fn mel_edges_are_evenly_spaced_in_mels() {
+        let edges = band_edges(FrequencyScale::Mel, 0.0, 8000.0, 38);
+        assert_eq!(edges.len(), 40);
+        assert!(edges[0].abs() < 1e-3 && (edges[39] - 8000.0).abs() < 0.5);
+        let step = hz_to_mel(8000.0) / 39.0;
+        for (i, edge) in edges.iter().enumerate() {
+            assert!((hz_to_mel(*edge) - step * i as f32).abs() < 0.01);
+        }
+        // 1000 Hz is 1000 mel by construction of the scale
+        assert!((hz_to_mel(1000.0) - 1000.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn full_scale_sine_reads_zero_dbfs() {
+        // 3000 Hz is bin 64 of a 1024-point FFT at 48 kHz; with 511 rows
+        // up to Nyquist every row sits on an FFT bin
+        let sample_rate = 48_000;
+        let samples: Vec<f32> = (0..48_000)
+            .map(|n| {
+                let amplitude = if n < 24_000 { 1.0 } else { 0.01 };
+                amplitude * (2.0 * std::f32::consts::PI * 3000.0 * n as f32 / 48_000.0).sin()
+            })
+            .collect();
+        let options = SpectrogramOptions {
+            bins: Some(511),
+            ..options(1024, 1024)
+        };
+        let spectrogram = compute(&samples, sample_rate, &options).unwrap();
+        assert!((spectrogram.frequencies[63] - 3000.0).abs() < 1e-2);
+        assert!(spectrogram.max_decibels.abs() < 0.1);
+
+        let row = |frame: usize| spectrogram.values[frame * 511 + 63];
+        assert!(row(10) >= 254);
+        // -40 dB is half of the 80 dB range
+        assert!((126..=129).contains(&row(35)), "{}", row(35));
+        // Far from the tone everything is below the floor
+        assert_eq!(spectrogram.values[10 * 511 + 300], 0);
+    }
+
+    #[test]
+    fn bytes_carry_a_json_header_then_the_values() {
+        let spectrogram = compute(&[0.5; 4096], 48_000, &options(256, 1024)).unwrap();
+        let bytes = spectrogram.to_bytes().unwrap();
+        let header_length = u32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize;
+        let header: serde_json::Value =
+            serde_json::from_slice(&bytes[4..4 + header_length]).unwrap();
+        assert_eq!(header["frameCount"], 4);
+        assert_eq!(header["binCount"], 128);
+        assert!(header.get("values").is_none());
+        assert_eq!(&bytes[4 + header_length..], spectrogram.values);
+    }
+
+    #[test]
+    fn default_bins_fit_the_largest_window() {
+        let spectrogram = compute(&[0.0; 40_000], 48_000, &options(32768, 8192)).unwrap();
+        assert_eq!(spectrogram.bin_count, MAX_BINS);
+    }
+
+    #[test]
+    fn too_many_bins_or_frames_are_refused() {
+        let bins = SpectrogramOptions {
+            bins: Some(MAX_BINS + 1),
+            ..options(1024, 512)
+        };
+        assert!(matches!(
+            compute(&[0.0; 1024], 48_000, &bins),
+            Err(AppError::InvalidArgument(_))
+        ));
+
+        // A minute at 48 kHz with a one-sample hop
+        let samples = vec![0.0f32; 48_000 * 60];
+        assert!(matches!(
+            compute(&samples, 48_000, &options(2048, 1)),
+            Err(AppError::InvalidArgument(_))
+        ));
+    }
+}
@@ -0,0 +1,225 @@
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use serde::{Deserialize, Serialize};
+
+use super::window::WindowFunction;
+use super::{fill_frame, frame_ends};
+use crate::errors::{AppError, AppResult};
+
+fn default_smoothing() -> f32 {
+    0.8
+}
+
+fn default_min_decibels() -> f32 {
+    -100.0
+}
+
+fn default_max_decibels() -> f32 {
+    -30.0
+}
+
+/// Analysis parameters. The defaults mirror `AnalyserNode` so the offline
+/// spectrum matches what the live preview shows.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpectrumOptions {
+    pub fft_size: usize,
+    #[serde(default)]
+    pub window: WindowFunction,
+    /// Hop in samples, used when `frame_rate` is not given (defaults to `fft_size / 2`).
+    pub hop: Option<usize>,
+    /// One spectrum per video frame at this rate.
+    pub frame_rate: Option<f64>,
+    #[serde(default = "default_smoothing")]
+    pub smoothing: f32,
+    #[serde(default = "default_min_decibels")]
+    pub min_decibels: f32,
+    #[serde(default = "default_max_decibels")]
+    pub max_decibels: f32,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpectrumFrames {
+    pub sample_rate: u32,
+    pub fft_size: usize,
+    pub bin_count: usize,
+    pub frame_count: usize,
+    pub frame_times: Vec<f64>,
+    /// `frame_count * bin_count` values in the 0–255 scale of `getByteFrequencyData`.
+    pub bins: Vec<u8>,
+}
+
+pub fn analyze(
+    samples: &[f32],
+    sample_rate: u32,
+    options: &SpectrumOptions,
+) -> AppResult<SpectrumFrames> {
+    let fft_size = options.fft_size;
+    if !fft_size.is_power_of_two() || !(32..=32768).contains(&fft_size) {
+        return Err(AppError::InvalidArgument(format!(
+            "fftSize must be a power of two between 32 and 32768, got {}",
+            fft_size
+        )));
+    }
+    if options.max_decibels <= options.min_decibels {
+        return Err(AppError::InvalidArgument(
+            "maxDecibels must be greater than minDecibels".to_string(),
+        ));
+    }
+    if !(0.0..1.0).contains(&options.smoothing) {
+        return Err(AppError::InvalidArgument(
+            "smoothing must be in [0, 1)".to_string(),
+        ));
+    }
+
+    let bin_count = fft_size / 2;
+    let hop = options.hop.unwrap_or(bin_count);
+    let ends = frame_ends(samples.len(), sample_rate, hop, options.frame_rate);
+
+    let window = options.window.coefficients(fft_size);
+    let fft = FftPlanner::<f32>::new().plan_fft_forward(fft_size);
+    let mut frame = vec![0.0f32; fft_size];
+    let mut buffer = vec![Complex::new(0.0f32, 0.0); fft_size];
+    let mut smoothed = vec![0.0f32; bin_count];
+    let mut bins = Vec::with_capacity(ends.len() * bin_count);
+    let range = options.max_decibels - options.min_decibels;
+
+    for &end in &ends {
+        fill_frame(samples, end, fft_size, &mut frame);
+        for ((slot, sample), w) in buffer.iter_mut().zip(&frame).zip(&window) {
+            *slot = Complex::new(sample * w, 0.0);
+        }
+        fft.process(&mut buffer);
+
+        for (k, value) in smoothed.iter_mut().enumerate() {
+            let magnitude = buffer[k].norm() / fft_size as f32;
+            *value = options.smoothing * *value + (1.0 - options.smoothing) * magnitude;
+            let db = 20.0 * value.log10();
+            let scaled = 255.0 * (db - options.min_decibels) / range;
+            bins.push(if scaled.is_finite() {
+                scaled.clamp(0.0, 255.0) as u8
+            } else {
+                0
+            });
+        }
+    }
+
+    Ok(SpectrumFrames {
+        sample_rate,
+        fft_size,
+        bin_count,
+        frame_count: ends.len(),
+        frame_times: ends
+            .iter()
+            .map(|&end| end as f64 / sample_rate as f64)
+            .collect(),
+        bins,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    fn options(window: WindowFunction, min_decibels: f32, max_decibels: f32) -> SpectrumOptions {
+        SpectrumOptions {
+            fft_size: 2048,
+            window,
+            hop: None,
+            frame_rate: None,
+            smoothing: 0.0,
+            min_decibels,
+            max_decibels,
+        }
+    }
+
+    /// A sine centred on FFT bin `bin`.
+    fn sine(bin: usize, amplitude: f32) -> Vec<f32> {
+        let freq = bin as f32 * SAMPLE_RATE as f32 / 2048.0;
+        (0..SAMPLE_RATE / 4)
+            .map(|i| {
+                amplitude
+                    * (2.0 * std::f32::consts::PI * freq * i as f32 / SAMPLE_RATE as f32).sin()
+            })
+            .collect()
+    }
+
+    fn last_frame(frames: &SpectrumFrames) -> &[u8] {
+        &frames.bins[(frames.frame_count - 1) * frames.bin_count..]
+    }
+
+    #[test]
+    fn sine_peaks_in_its_bin() {
+        for bin in [10, 40, 300] {
+            // A range wide enough that the peak does not saturate at 255
+            let frames = analyze(
+                &sine(bin, 0.5),
+                SAMPLE_RATE,
+                &options(WindowFunction::Blackman, -100.0, 0.0),
+            )
+            .unwrap();
+            let spectrum = last_frame(&frames);
+            let peak = (0..spectrum.len()).max_by_key(|&k| spectrum[k]).unwrap();
+            assert_eq!(peak, bin);
+        }
+    }
+
+    #[test]
+    fn byte_scale_matches_analyser_node() {
+        // AnalyserNode: floor(255 / (maxDecibels - minDecibels) * (dB - minDecibels)), clamped to 0–255.
+        // With a rectangular window a bin-centred sine of amplitude A reads A / 2.
+        for (amplitude, min_decibels, max_decibels) in [
+            (1.0, -100.0, 0.0),
+            (0.1, -100.0, -30.0),
+            (0.02, -80.0, -10.0),
+        ] {
+            let frames = analyze(
+                &sine(64, amplitude),
+                SAMPLE_RATE,
+                &options(WindowFunction::Rectangular, min_decibels, max_decibels),
+            )
+            .unwrap();
+            let db = 20.0 * (amplitude / 2.0).log10();
+            let expected =
+                (255.0 * (db - min_decibels) / (max_decibels - min_decibels)).clamp(0.0, 255.0);
+            let actual = last_frame(&frames)[64] as f32;
+            assert!(
+                (actual - expected.floor()).abs() <= 1.0,
+                "{} vs {}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn byte_scale_clamps_silence_and_overload() {
+        let silent = analyze(
+            &vec![0.0; 8192],
+            SAMPLE_RATE,
+            &options(WindowFunction::Blackman, -100.0, -30.0),
+        )
+        .unwrap();
+        assert!(silent.bins.iter().all(|&b| b == 0));
+
+        let loud = analyze(
+            &sine(64, 1.0),
+            SAMPLE_RATE,
+            &options(WindowFunction::Rectangular, -100.0, -30.0),
+        )
+        .unwrap();
+        assert_eq!(last_frame(&loud)[64], 255);
+    }
+
+    #[test]
+    fn frame_rate_gives_one_frame_per_video_frame() {
+        let mut opts = options(WindowFunction::Blackman, -100.0, -30.0);
+        opts.frame_rate = Some(30.0);
+        let frames = analyze(&vec![0.0; SAMPLE_RATE as usize * 2], SAMPLE_RATE, &opts).unwrap();
+        assert_eq!(frames.frame_count, 60);
+        assert!((frames.frame_times[30] - 1.0).abs() < 1e-9);
+    }
+}
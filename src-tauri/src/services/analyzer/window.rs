@@ -0,0 +1,31 @@
+use std::f32::consts::PI;
+
+use serde::Deserialize;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowFunction {
+    /// Same window `AnalyserNode` uses, so it is the default.
+    #[default]
+    Blackman,
+    Hann,
+    Hamming,
+    Rectangular,
+}
+
+impl WindowFunction {
+    pub fn coefficients(self, size: usize) -> Vec<f32> {
+        let n = size as f32;
+        (0..size)
+            .map(|i| {
+                let x = 2.0 * PI * i as f32 / n;
+                match self {
+                    WindowFunction::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+                    WindowFunction::Hann => 0.5 - 0.5 * x.cos(),
+                    WindowFunction::Hamming => 0.54 - 0.46 * x.cos(),
+                    WindowFunction::Rectangular => 1.0,
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,41 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::decoder::DecodedAudio;
+use crate::errors::{AppError, AppResult};
+
+/// Decoded audio kept alive between commands, addressed by a numeric handle.
+#[derive(Default)]
+pub struct AudioStore {
+    next_handle: AtomicU32,
+    entries: Mutex<HashMap<u32, Arc<DecodedAudio>>>,
+}
+
+impl AudioStore {
+    pub fn insert(&self, audio: DecodedAudio) -> u32 {
+        let handle = self.next_handle.fetch_add(1, Ordering::Relaxed) + 1;
+        self.entries
+            .lock()
+            .expect("audio store poisoned")
+            .insert(handle, Arc::new(audio));
+        handle
+    }
+
+    pub fn get(&self, handle: u32) -> AppResult<Arc<DecodedAudio>> {
+        self.entries
+            .lock()
+            .expect("audio store poisoned")
+            .get(&handle)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("Unknown audio handle: {}", handle)))
+    }
+
+    pub fn remove(&self, handle: u32) -> bool {
+        self.entries
+            .lock()
+            .expect("audio store poisoned")
+            .remove(&handle)
+            .is_some()
+    }
+}
@@ -0,0 +1,334 @@
+use colorous::Gradient;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::{AppError, AppResult};
+
+mod tables;
+
+/// Entries in a lookup table; one per 0–255 intensity.
+pub const LUT_SIZE: usize = 256;
+
+/// Built-in color maps. Viridis, magma, inferno and plasma are matplotlib's
+/// own tables; cividis and turbo sample the `colorous` polynomial fits, which
+/// can be a step or two off the published tables. `hot` and `cool` are kept
+/// as gradients for layers saved before they existed.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Colormap {
+    #[default]
+    Viridis,
+    Magma,
+    Inferno,
+    Plasma,
+    Cividis,
+    Turbo,
+    Grayscale,
+    Hot,
+    Cool,
+}
+
+impl Colormap {
+    fn table(self) -> Option<&'static [u32; LUT_SIZE]> {
+        match self {
+            Colormap::Viridis => Some(&tables::VIRIDIS),
+            Colormap::Magma => Some(&tables::MAGMA),
+            Colormap::Inferno => Some(&tables::INFERNO),
+            Colormap::Plasma => Some(&tables::PLASMA),
+            _ => None,
+        }
+    }
+
+    fn gradient(self) -> Option<Gradient> {
+        match self {
+            Colormap::Cividis => Some(colorous::CIVIDIS),
+            Colormap::Turbo => Some(colorous::TURBO),
+            _ => None,
+        }
+    }
+
+    fn stops(self) -> Vec<GradientStop> {
+        let stop = |position, color: &str| GradientStop {
+            position,
+            color: color.to_string(),
+        };
+        match self {
+            Colormap::Hot => vec![
+                stop(0.0, "#000000"),
+                stop(1.0 / 3.0, "#ff0000"),
+                stop(2.0 / 3.0, "#ffff00"),
+                stop(1.0, "#ffffff"),
+            ],
+            Colormap::Cool => vec![
+                stop(0.0, "#0000ff"),
+                stop(0.5, "#00ff00"),
+                stop(1.0, "#ff0000"),
+            ],
+            _ => vec![stop(0.0, "#000000"), stop(1.0, "#ffffff")],
+        }
+    }
+}
+
+/// A color at `position` (0–1) along a user-defined gradient.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GradientStop {
+    pub position: f32,
+    /// `#rrggbb`
+    pub color: String,
+}
+
+/// A built-in map by name, or `{ "stops": [...] }` for a custom gradient.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ColormapSpec {
+    Named(Colormap),
+    Custom { stops: Vec<GradientStop> },
+}
+
+impl Default for ColormapSpec {
+    fn default() -> Self {
+        ColormapSpec::Named(Colormap::default())
+    }
+}
+
+/// RGB for every intensity from 0 (quietest) to 255 (loudest).
+pub struct Lut([[u8; 3]; LUT_SIZE]);
+
+impl Lut {
+    pub fn color(&self, intensity: u8) -> [u8; 3] {
+        self.0[intensity as usize]
+    }
+
+    /// `LUT_SIZE * 3` bytes, RGB per entry.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.iter().flatten().copied().collect()
+    }
+}
+
+/// Parse a `#rrggbb` color.
+pub fn parse_hex(color: &str) -> AppResult<[u8; 3]> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    let invalid =
+        || AppError::InvalidArgument(format!("Invalid color {:?}; expected #rrggbb", color));
+    // from_str_radix alone would take a sign, as in "#+1+2+3"
+    if hex.len() != 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+    let mut rgb = [0u8; 3];
+    for (channel, value) in rgb.iter_mut().enumerate() {
+        *value =
+            u8::from_str_radix(&hex[channel * 2..channel * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(rgb)
+}
+
+/// Linear interpolation between validated stops, clamped at both ends.
+fn gradient_lut(stops: &[GradientStop]) -> AppResult<Lut> {
+    if stops.len() < 2 {
+        return Err(AppError::InvalidArgument(
+            "A gradient needs at least two stops".to_string(),
+        ));
+    }
+    let mut parsed = Vec::with_capacity(stops.len());
+    for stop in stops {
+        if !(0.0..=1.0).contains(&stop.position) {
+            return Err(AppError::InvalidArgument(format!(
+                "Gradient stop position must be between 0 and 1, got {}",
+                stop.position
+            )));
+        }
+        parsed.push((stop.position, parse_hex(&stop.color)?.map(f32::from)));
+    }
+    parsed.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut lut = [[0u8; 3]; LUT_SIZE];
+    for (i, entry) in lut.iter_mut().enumerate() {
+        let t = i as f32 / (LUT_SIZE - 1) as f32;
+        let upper = parsed
+            .iter()
+            .position(|(position, _)| *position >= t)
+            .unwrap_or(parsed.len() - 1);
+        let lower = upper.saturating_sub(1);
+        let (p0, c0) = parsed[lower];
+        let (p1, c1) = parsed[upper];
+        let f = if p1 > p0 {
+            ((t - p0) / (p1 - p0)).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        for channel in 0..3 {
+            entry[channel] = (c0[channel] + (c1[channel] - c0[channel]) * f).round() as u8;
+        }
+    }
+    Ok(Lut(lut))
+}
+
+/// Build the lookup table for a named map or custom gradient.
+pub fn lut(spec: &ColormapSpec) -> AppResult<Lut> {
+    match spec {
+        ColormapSpec::Named(Colormap::Grayscale) => {
+            let mut lut = [[0u8; 3]; LUT_SIZE];
+            for (i, entry) in lut.iter_mut().enumerate() {
+                *entry = [i as u8; 3];
+            }
+            Ok(Lut(lut))
+        }
+        ColormapSpec::Named(map) => {
+            if let Some(table) = map.table() {
+                Ok(Lut(table.map(|rgb| {
+                    [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8]
+                })))
+            } else if let Some(gradient) = map.gradient() {
+                let mut lut = [[0u8; 3]; LUT_SIZE];
+                for (i, entry) in lut.iter_mut().enumerate() {
+                    let color = gradient.eval_rational(i, LUT_SIZE);
+                    *entry = [color.r, color.g, color.b];
+                }
+                Ok(Lut(lut))
+            } else {
+                gradient_lut(&map.stops())
+            }
+        }
+        ColormapSpec::Custom { stops } => gradient_lut(stops),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(map: Colormap) -> Lut {
+        lut(&ColormapSpec::Named(map)).unwrap()
+    }
+
+    /// CIE L* of an sRGB color.
+    fn lightness([r, g, b]: [u8; 3]) -> f64 {
+        let linear = |c: u8| {
+            let c = f64::from(c) / 255.0;
+            if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        let y = 0.2126 * linear(r) + 0.7152 * linear(g) + 0.0722 * linear(b);
+        if y > 216.0 / 24389.0 {
+            116.0 * y.cbrt() - 16.0
+        } else {
+            y * 24389.0 / 27.0
+        }
+    }
+
+    #[test]
+    fn tables_end_on_the_published_colors() {
+        // matplotlib's first and last entries, rounded to 8 bits
+        let cases = [
+            (Colormap::Viridis, [68, 1, 84], [253, 231, 37]),
+            (Colormap::Magma, [0, 0, 4], [252, 253, 191]),
+            (Colormap::Inferno, [0, 0, 4], [252, 255, 164]),
+            (Colormap::Plasma, [13, 8, 135], [240, 249, 33]),
+        ];
+        for (map, first, last) in cases {
+            let lut = named(map);
+            assert_eq!(lut.color(0), first, "{:?}", map);
+            assert_eq!(lut.color(255), last, "{:?}", map);
+        }
+    }
+
+    #[test]
+    fn perceptual_maps_get_lighter() {
+        for map in [
+            Colormap::Viridis,
+            Colormap::Magma,
+            Colormap::Inferno,
+            Colormap::Plasma,
+            Colormap::Cividis,
+        ] {
+            let lut = named(map);
+            for i in 1..LUT_SIZE {
+                let (before, after) = (lut.color(i as u8 - 1), lut.color(i as u8));
+                // 8-bit rounding can dip by a fraction of a unit
+                assert!(
+                    lightness(after) > lightness(before) - 0.5,
+                    "{:?} darkens at {}: {:?} -> {:?}",
+                    map,
+                    i,
+                    before,
+                    after
+                );
+            }
+            assert!(lightness(lut.color(255)) - lightness(lut.color(0)) > 50.0);
+        }
+    }
+
+    #[test]
+    fn gradient_interpolates_between_sorted_stops() {
+        let stops = vec![
+            GradientStop {
+                position: 1.0,
+                color: "#ffffff".to_string(),
+            },
+            GradientStop {
+                position: 0.5,
+                color: "#ff0000".to_string(),
+            },
+            GradientStop {
+                position: 0.0,
+                color: "#000000".to_string(),
+            },
+        ];
+        let lut = lut(&ColormapSpec::Custom { stops }).unwrap();
+        assert_eq!(lut.color(0), [0, 0, 0]);
+        assert_eq!(lut.color(64), [128, 0, 0]);
+        assert_eq!(lut.color(255), [255, 255, 255]);
+        assert_eq!(lut.to_bytes().len(), LUT_SIZE * 3);
+    }
+
+    #[test]
+    fn gradient_clamps_outside_its_stops() {
+        let stops = vec![
+            GradientStop {
+                position: 0.25,
+                color: "#0000ff".to_string(),
+            },
+            GradientStop {
+                position: 0.75,
+                color: "#00ff00".to_string(),
+            },
+        ];
+        let lut = lut(&ColormapSpec::Custom { stops }).unwrap();
+        assert_eq!(lut.color(0), [0, 0, 255]);
+        assert_eq!(lut.color(255), [0, 255, 0]);
+    }
+
+    #[test]
+    fn gradient_refuses_bad_stops() {
+        let stop = |position, color: &str| GradientStop {
+            position,
+            color: color.to_string(),
+        };
+        for stops in [
+            vec![stop(0.0, "#000000")],
+            vec![stop(0.0, "#000000"), stop(1.5, "#ffffff")],
+            vec![stop(0.0, "#000000"), stop(f32::NAN, "#ffffff")],
+            vec![stop(0.0, "#000000"), stop(1.0, "white")],
+        ] {
+            assert!(matches!(
+                lut(&ColormapSpec::Custom { stops }),
+                Err(AppError::InvalidArgument(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn parse_hex_takes_only_six_hex_digits() {
+        assert_eq!(parse_hex("#1a2B3c").unwrap(), [0x1a, 0x2b, 0x3c]);
+        assert_eq!(parse_hex("ffffff").unwrap(), [255, 255, 255]);
+        for bad in ["#+1+2+3", "#fff", "#1234567", "#12345g", "#12 345", "#ééé"] {
+            assert!(
+                matches!(parse_hex(bad), Err(AppError::InvalidArgument(_))),
+                "{:?}",
+                bad
+            );
+        }
+    }
+}
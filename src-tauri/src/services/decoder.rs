@@ -0,0 +1,208 @@
+use std::fs::File;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use symphonia::core::audio::{SampleBuffer, SignalSpec};
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::errors::{AppError, AppResult};
+
+/// PCM decoded from an audio file, stored planar (one buffer per channel).
+pub struct DecodedAudio {
+    pub path: PathBuf,
+    pub sample_rate: u32,
+    pub channels: Vec<Vec<f32>>,
+    /// `path` is a temporary file of ours, deleted with the audio.
+    pub temporary: bool,
+}
+
+impl DecodedAudio {
+    pub fn frames(&self) -> usize {
+        self.channels.first().map_or(0, Vec::len)
+    }
+
+    pub fn duration(&self) -> f64 {
+        if self.sample_rate == 0 {
+            return 0.0;
+        }
+        self.frames() as f64 / self.sample_rate as f64
+    }
+
+    /// Average of all channels, used as the input of the analyzers.
+    pub fn mixdown(&self) -> Vec<f32> {
+        let count = self.channels.len().max(1) as f32;
+        let mut mono = vec![0.0f32; self.frames()];
+        for channel in &self.channels {
+            for (out, sample) in mono.iter_mut().zip(channel) {
+                *out += sample / count;
+            }
+        }
+        mono
+    }
+}
+
+impl Drop for DecodedAudio {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Decode a WAV/MP3/FLAC/OGG/AAC file into PCM without going through the WebView.
+pub fn decode_file(path: &Path) -> AppResult<DecodedAudio> {
+    let file = File::open(path).map_err(|e| AppError::io(path, "open", e))?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| AppError::UnsupportedFormat(format!("{}: {}", path.display(), e)))?;
+    let mut format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| {
+            AppError::UnsupportedFormat(format!("{}: no audio track found", path.display()))
+        })?;
+    let track_id = track.id;
+    let mut sample_rate = track.codec_params.sample_rate.unwrap_or(0);
+
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| AppError::UnsupportedFormat(format!("{}: {}", path.display(), e)))?;
+
+    let mut channels: Vec<Vec<f32>> = Vec::new();
+    let mut first_spec: Option<SignalSpec> = None;
+    let mut sample_buf: Option<(SignalSpec, SampleBuffer<f32>)> = None;
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(SymphoniaError::ResetRequired) => break,
+            Err(e) => return Err(AppError::Decode(e.to_string())),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A corrupt frame should not abort the whole file
+            Err(SymphoniaError::DecodeError(_)) => continue,
+            Err(e) => return Err(AppError::Decode(e.to_string())),
+        };
+
+        let spec = *decoded.spec();
+        let channel_count = spec.channels.count();
+        let frames = decoded.frames();
+        match first_spec {
+            None => {
+                first_spec = Some(spec);
+                channels = vec![Vec::new(); channel_count];
+                sample_rate = spec.rate;
+            }
+            // Chained Ogg streams or MP3 frames that switch between mono and stereo
+            Some(first) if first != spec => {
+                return Err(AppError::Decode(format!(
+                    "{}: the audio changes from {} channels at {} Hz to {} channels at {} Hz",
+                    path.display(),
+                    first.channels.count(),
+                    first.rate,
+                    channel_count,
+                    spec.rate
+                )));
+            }
+            Some(_) => {}
+        }
+
+        let needed = decoded.capacity() * channel_count;
+        if sample_buf
+            .as_ref()
+            .is_none_or(|(buf_spec, buf)| *buf_spec != spec || buf.capacity() < needed)
+        {
+            sample_buf = Some((spec, SampleBuffer::new(decoded.capacity() as u64, spec)));
+        }
+        let (_, buf) = sample_buf
+            .as_mut()
+            .expect("sample buffer was just allocated");
+        buf.copy_planar_ref(decoded);
+
+        for (index, channel) in channels.iter_mut().enumerate() {
+            channel.extend_from_slice(&buf.samples()[index * frames..(index + 1) * frames]);
+        }
+    }
+
+    if channels.is_empty() {
+        return Err(AppError::Decode(format!(
+            "no audio samples in {}",
+            path.display()
+        )));
+    }
+
+    Ok(DecodedAudio {
+        path: path.to_path_buf(),
+        sample_rate,
+        channels,
+        temporary: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wav_round_trip() {
+        let path = std::env::temp_dir().join(format!(
+            "music-visualizer-decoder-{}.wav",
+            std::process::id()
+        ));
+        let left: Vec<f32> = (0..1000).map(|n| (n as f32 / 1000.0) - 0.5).collect();
+        let right: Vec<f32> = left.iter().map(|s| -s * 0.25).collect();
+        let spec = hound::WavSpec {
+            channels: 2,
+            sample_rate: 22_050,
+            bits_per_sample: 32,
+            sample_format: hound::SampleFormat::Float,
+        };
+        let mut writer = hound::WavWriter::create(&path, spec).unwrap();
+        for (l, r) in left.iter().zip(&right) {
+            writer.write_sample(*l).unwrap();
+            writer.write_sample(*r).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let audio = decode_file(&path);
+        let _ = std::fs::remove_file(&path);
+        let audio = audio.unwrap();
+        assert_eq!(audio.sample_rate, 22_050);
+        assert_eq!(audio.channels, [left, right]);
+        assert!((audio.duration() - 1000.0 / 22_050.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn missing_file_is_an_io_error() {
+        assert!(matches!(
+            decode_file(Path::new("/nonexistent/music-visualizer.wav")),
+            Err(AppError::Io { .. })
+        ));
+    }
+}
@@ -0,0 +1,228 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ExitStatus, Stdio};
+use std::sync::{Mutex, PoisonError};
+use std::thread::JoinHandle;
+
+use serde::Deserialize;
+
+use super::ffmpeg;
+use super::ffmpeg::container::Container;
+use super::ffmpeg::jobs::ConversionHandle;
+use super::ffmpeg::normalize::LoudnessTarget;
+use super::ffmpeg::output::same_file;
+use super::ffmpeg::profile::{resolve_profile, ProfileSpec};
+use super::ffmpeg::progress::ConversionProgress;
+use crate::errors::{AppError, AppResult};
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RenderRequest {
+    pub output_path: String,
+    pub audio_path: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub frame_rate: f64,
+    /// Quality preset name or full `ExportProfile`; defaults to `medium`.
+    #[serde(default)]
+    pub profile: Option<ProfileSpec>,
+    /// Normalize the audio to this loudness before muxing it.
+    #[serde(default)]
+    pub loudness: Option<LoudnessTarget>,
+}
+
+/// An FFmpeg process fed with raw RGBA frames on stdin.
+///
+/// Frames are timestamped by FFmpeg from `frame_rate`, so the output has
+/// exactly one video frame per pushed frame regardless of how long each
+/// frame took to render.
+///
+/// The pipe and the process are locked separately, so `cancel` can kill
+/// FFmpeg while a frame write is blocked on a full pipe.
+pub struct RenderJob {
+    child: Mutex<Child>,
+    sink: Mutex<FrameSink>,
+    stderr: Mutex<Option<JoinHandle<String>>>,
+    frame_bytes: usize,
+    output_path: PathBuf,
+}
+
+/// FFmpeg's stdin, `None` once it is closed.
+struct FrameSink {
+    stdin: Option<ChildStdin>,
+    frames_written: u64,
+}
+
+impl RenderJob {
+    /// Start FFmpeg (`ffmpeg_path` if configured). A request without a
+    /// profile uses `default_profile`, then `medium`. Loudness normalization
+    /// measures the whole audio file first, so this can block for a while;
+    /// that measurement runs under `handle` and reports to `on_progress`.
+    pub fn spawn(
+        request: &RenderRequest,
+        ffmpeg_path: Option<&Path>,
+        default_profile: Option<ProfileSpec>,
+        handle: &ConversionHandle,
+        job_id: &str,
+        on_progress: impl FnMut(ConversionProgress),
+    ) -> AppResult<Self> {
+        if request.width == 0 || request.height == 0 {
+            return Err(AppError::InvalidArgument(
+                "Render size must not be zero".to_string(),
+            ));
+        }
+        if !request.frame_rate.is_finite() || request.frame_rate <= 0.0 {
+            return Err(AppError::InvalidArgument(
+                "Frame rate must be positive".to_string(),
+            ));
+        }
+
+        let output_path = PathBuf::from(&request.output_path);
+        if let Some(audio_path) = &request.audio_path {
+            if same_file(Path::new(audio_path), &output_path) {
+                return Err(AppError::InvalidArgument(
+                    "The video cannot be written over its own audio file".to_string(),
+                ));
+            }
+        }
+        let container = Container::from_path(&output_path)?;
+        let profile = resolve_profile(request.profile.clone().or(default_profile), &output_path)?;
+        if profile.two_pass && container != Container::Gif {
+            return Err(AppError::InvalidArgument(
+                "Two-pass encoding is not available for live-rendered frames".to_string(),
+            ));
+        }
+
+        let mut command = ffmpeg::command(ffmpeg_path);
+        command
+            .args(["-hide_banner", "-loglevel", "error", "-nostats"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+            .arg("-s")
+            .arg(format!("{}x{}", request.width, request.height))
+            .arg("-framerate")
+            .arg(request.frame_rate.to_string())
+            .args(["-i", "pipe:0"]);
+        if container == Container::Gif {
+            // GIF has no audio track, so the audio input is not even opened
+            command.args(Container::gif_args());
+        } else {
+            if let Some(audio_path) = &request.audio_path {
+                let audio_filter = match &request.loudness {
+                    Some(target) if profile.audio_encoder_name().is_some() => target
+                        .audio_filter_args(
+                            ffmpeg_path,
+                            Path::new(audio_path),
+                            handle,
+                            job_id,
+                            on_progress,
+                        )?,
+                    _ => Vec::new(),
+                };
+                command
+                    .arg("-i")
+                    .arg(audio_path)
+                    .args(["-map", "0:v", "-map", "1:a", "-shortest"])
+                    .args(profile.audio_args())
+                    .args(audio_filter);
+            }
+            command.args(profile.video_args());
+        }
+        command
+            .arg("-y")
+            .arg(&output_path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+
+        let mut child = command.spawn().map_err(AppError::ffmpeg_spawn)?;
+
+        // Drain stderr on a thread so a chatty FFmpeg never blocks on a full pipe
+        let stderr = child.stderr.take().map(ffmpeg::drain);
+
+        Ok(RenderJob {
+            sink: Mutex::new(FrameSink {
+                stdin: child.stdin.take(),
+                frames_written: 0,
+            }),
+            child: Mutex::new(child),
+            stderr: Mutex::new(stderr),
+            frame_bytes: request.width as usize * request.height as usize * 4,
+            output_path,
+        })
+    }
+
+    /// Write one frame; blocks while FFmpeg's pipe is full.
+    pub fn write_frame(&self, rgba: &[u8]) -> AppResult<u64> {
+        if rgba.len() != self.frame_bytes {
+            return Err(AppError::InvalidArgument(format!(
+                "Frame has {} bytes, expected {}",
+                rgba.len(),
+                self.frame_bytes
+            )));
+        }
+        let mut sink = self.sink.lock().expect("render job poisoned");
+        let stdin = sink.stdin.as_mut().ok_or_else(|| {
+            AppError::InvalidArgument("Render job is already finished".to_string())
+        })?;
+        if stdin.write_all(rgba).is_err() {
+            // FFmpeg exited (usually a broken pipe); its stderr says why
+            sink.stdin = None;
+            let (status, stderr) = self.wait()?;
+            return Err(AppError::ffmpeg_failed(status, &stderr));
+        }
+        sink.frames_written += 1;
+        Ok(sink.frames_written)
+    }
+
+    /// Wait for FFmpeg to exit and collect its stderr.
+    fn wait(&self) -> AppResult<(ExitStatus, String)> {
+        let status = self
+            .child
+            .lock()
+            .expect("render job poisoned")
+            .wait()
+            .map_err(|e| AppError::Internal(format!("Failed to wait for FFmpeg: {}", e)))?;
+        let stderr = self
+            .stderr
+            .lock()
+            .expect("render job poisoned")
+            .take()
+            .and_then(|handle| handle.join().ok())
+            .unwrap_or_default();
+        Ok((status, stderr))
+    }
+
+    /// Close stdin and wait for FFmpeg to finish muxing.
+    pub fn finish(&self) -> AppResult<String> {
+        drop(self.sink.lock().expect("render job poisoned").stdin.take());
+        let (status, stderr) = self.wait()?;
+
+        if status.success() {
+            Ok(self.output_path.to_string_lossy().into_owned())
+        } else {
+            Err(AppError::ffmpeg_failed(status, &stderr))
+        }
+    }
+
+    /// Kill FFmpeg (which also unblocks a pending frame write) and remove the partial output.
+    pub fn cancel(&self) {
+        let _ = self.child.lock().expect("render job poisoned").kill();
+        drop(self.sink.lock().expect("render job poisoned").stdin.take());
+        let _ = self.child.lock().expect("render job poisoned").wait();
+        let _ = std::fs::remove_file(&self.output_path);
+    }
+}
+
+impl Drop for RenderJob {
+    /// A job dropped without `finish` or `cancel` (window closed, store
+    /// cleared) must not leave FFmpeg running.
+    fn drop(&mut self) {
+        let sink = self.sink.get_mut().unwrap_or_else(PoisonError::into_inner);
+        drop(sink.stdin.take());
+        let child = self.child.get_mut().unwrap_or_else(PoisonError::into_inner);
+        if let Ok(None) = child.try_wait() {
+            let _ = child.kill();
+        }
+        let _ = child.wait();
+    }
+}
@@ -0,0 +1,221 @@
+use std::path::Path;
+
+use super::profile::{output_extension, AudioCodec, ExportProfile, VideoCodec};
+use crate::errors::{AppError, AppResult};
+
+/// Output containers the app can write, chosen by file extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Container {
+    Mp4,
+    Mov,
+    Webm,
+    Mkv,
+    /// Animated GIF; encoded through a palette filter instead of a codec.
+    Gif,
+}
+
+impl Container {
+    pub const ALL: [Container; 5] = [
+        Container::Mp4,
+        Container::Mov,
+        Container::Webm,
+        Container::Mkv,
+        Container::Gif,
+    ];
+
+    pub fn from_path(path: &Path) -> AppResult<Self> {
+        match output_extension(path).as_deref() {
+            Some("mp4" | "m4v") => Ok(Container::Mp4),
+            Some("mov") => Ok(Container::Mov),
+            Some("webm") => Ok(Container::Webm),
+            Some("mkv") => Ok(Container::Mkv),
+            Some("gif") => Ok(Container::Gif),
+            Some(other) => Err(AppError::UnsupportedFormat(format!(
+                "Cannot export .{} files",
+                other
+            ))),
+            None => Err(AppError::UnsupportedFormat(format!(
+                "{} has no file extension to pick a format from",
+                path.display()
+            ))),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Mov => "mov",
+            Container::Webm => "webm",
+            Container::Mkv => "mkv",
+            Container::Gif => "gif",
+        }
+    }
+
+    /// The FFmpeg muxer (`-f`) that writes this container.
+    pub fn muxer_name(self) -> &'static str {
+        match self {
+            Container::Mkv => "matroska",
+            other => other.name(),
+        }
+    }
+
+    /// Video codecs FFmpeg's muxer for this container accepts without `-strict`.
+    pub fn video_codecs(self) -> &'static [VideoCodec] {
+        use VideoCodec::*;
+        match self {
+            Container::Mp4 => &[X264, X265, Vp9, Av1],
+            Container::Mov => &[X264, X265, ProRes],
+            Container::Webm => &[Vp9, Av1],
+            Container::Mkv => &[X264, X265, Vp9, Av1, ProRes],
+            Container::Gif => &[],
+        }
+    }
+
+    /// Audio codecs the container accepts. `None` (no audio) always fits.
+    pub fn audio_codecs(self) -> &'static [AudioCodec] {
+        use AudioCodec::*;
+        match self {
+            Container::Mp4 => &[Aac, Opus, None],
+            Container::Mov => &[Aac, Pcm, None],
+            Container::Webm => &[Opus, None],
+            Container::Mkv => &[Aac, Opus, Pcm, Flac, None],
+            Container::Gif => &[None],
+        }
+    }
+
+    /// Check `profile` against this container before FFmpeg is started, so a
+    /// bad combination fails immediately instead of after a muxer error.
+    pub fn check(self, profile: &ExportProfile) -> AppResult<()> {
+        // GIF ignores the profile's codecs entirely
+        if self == Container::Gif {
+            return Ok(());
+        }
+        if !self.video_codecs().contains(&profile.video_codec) {
+            return Err(AppError::IncompatibleCodec {
+                container: self.name().to_string(),
+                codec: codec_name(profile.video_codec),
+                supported: self.video_codecs().iter().map(|&c| codec_name(c)).collect(),
+            });
+        }
+        if !self.audio_codecs().contains(&profile.audio_codec) {
+            return Err(AppError::IncompatibleCodec {
+                container: self.name().to_string(),
+                codec: codec_name(profile.audio_codec),
+                supported: self.audio_codecs().iter().map(|&c| codec_name(c)).collect(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Arguments that replace the profile's video/audio arguments for GIF:
+    /// a palette generated from the clip itself (in one pass via `split`)
+    /// keeps banding down, and GIF has no audio.
+    pub fn gif_args() -> Vec<String> {
+        vec![
+            "-filter_complex".into(),
+            "[0:v]split[a][b];[a]palettegen=stats_mode=diff[p];[b][p]paletteuse=dither=sierra2_4a"
+                .into(),
+            "-loop".into(),
+            "0".into(),
+            "-an".into(),
+        ]
+    }
+}
+
+/// The frontend's name for a codec (its serde value).
+fn codec_name<T: serde::Serialize>(codec: T) -> String {
+    serde_json::to_value(codec)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::ffmpeg::profile::{ProResProfile, QualityPreset};
+
+    fn profile(video_codec: VideoCodec, audio_codec: AudioCodec) -> ExportProfile {
+        ExportProfile {
+            video_codec,
+            audio_codec,
+            ..QualityPreset::Medium.profile(Path::new("out.mp4"))
+        }
+    }
+
+    #[test]
+    fn webm_refuses_x264() {
+        let error = Container::Webm
+            .check(&profile(VideoCodec::X264, AudioCodec::Opus))
+            .unwrap_err();
+        assert_eq!(
+            error,
+            AppError::IncompatibleCodec {
+                container: "webm".to_string(),
+                codec: "x264".to_string(),
+                supported: vec!["vp9".to_string(), "av1".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn mp4_refuses_pcm_audio() {
+        let error = Container::Mp4
+            .check(&profile(VideoCodec::X264, AudioCodec::Pcm))
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            AppError::IncompatibleCodec { ref container, ref codec, .. }
+                if container == "mp4" && codec == "pcm"
+        ));
+    }
+
+    #[test]
+    fn mov_takes_prores() {
+        let prores = ExportProfile {
+            prores_profile: Some(ProResProfile::P4444),
+            ..profile(VideoCodec::ProRes, AudioCodec::Pcm)
+        };
+        assert!(Container::Mov.check(&prores).is_ok());
+    }
+
+    #[test]
+    fn gif_ignores_the_codecs() {
+        assert!(Container::Gif
+            .check(&profile(VideoCodec::ProRes, AudioCodec::Flac))
+            .is_ok());
+    }
+
+    #[test]
+    fn gif_builds_its_palette_in_one_pass() {
+        assert_eq!(
+            Container::gif_args(),
+            [
+                "-filter_complex",
+                "[0:v]split[a][b];[a]palettegen=stats_mode=diff[p];[b][p]paletteuse=dither=sierra2_4a",
+                "-loop",
+                "0",
+                "-an",
+            ]
+        );
+    }
+
+    #[test]
+    fn container_from_extension() {
+        assert_eq!(
+            Container::from_path(Path::new("a/b.M4V")).unwrap(),
+            Container::Mp4
+        );
+        assert_eq!(
+            Container::from_path(Path::new("clip.mkv"))
+                .unwrap()
+                .muxer_name(),
+            "matroska"
+        );
+        assert!(matches!(
+            Container::from_path(Path::new("clip.avi")),
+            Err(AppError::UnsupportedFormat(_))
+        ));
+        assert!(Container::from_path(Path::new("clip")).is_err());
+    }
+}
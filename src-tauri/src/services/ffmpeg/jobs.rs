@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::process::{Child, ExitStatus};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::errors::{AppError, AppResult};
+
+/// A conversion that can be stopped from another command. It stays
+/// registered across all of its FFmpeg passes; `child` is the current one.
+pub struct ConversionHandle {
+    child: Mutex<Option<Child>>,
+    cancelled: AtomicBool,
+}
+
+impl ConversionHandle {
+    pub fn cancel(&self) {
+        let mut child = self.child.lock().expect("conversion handle poisoned");
+        self.cancelled.store(true, Ordering::SeqCst);
+        if let Some(child) = child.as_mut() {
+            let _ = child.kill();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Make `child` the running pass. It is killed at once if the job was
+    /// cancelled while it was being spawned.
+    pub fn attach(&self, mut child: Child) {
+        let mut slot = self.child.lock().expect("conversion handle poisoned");
+        if self.is_cancelled() {
+            let _ = child.kill();
+        }
+        *slot = Some(child);
+    }
+
+    /// Wait for the current pass; call only after its stdout has been drained.
+    pub fn wait(&self) -> std::io::Result<ExitStatus> {
+        let mut slot = self.child.lock().expect("conversion handle poisoned");
+        let status = match slot.as_mut() {
+            Some(child) => child.wait(),
+            None => Err(std::io::Error::other("No FFmpeg process is running")),
+        };
+        *slot = None;
+        status
+    }
+}
+
+/// Conversions in progress, keyed by the job id the frontend chose.
+#[derive(Clone, Default)]
+pub struct ConversionJobs {
+    next_id: Arc<AtomicU32>,
+    jobs: Arc<Mutex<HashMap<String, Arc<ConversionHandle>>>>,
+}
+
+impl ConversionJobs {
+    pub fn generate_id(&self) -> String {
+        format!(
+            "conversion-{}",
+            self.next_id.fetch_add(1, Ordering::Relaxed) + 1
+        )
+    }
+
+    /// Register a conversion before its first pass; `remove` it after the last.
+    /// Fails if a running conversion already has this id.
+    pub fn register(&self, job_id: &str) -> AppResult<Arc<ConversionHandle>> {
+        let mut jobs = self.jobs.lock().expect("conversion jobs poisoned");
+        if jobs.contains_key(job_id) {
+            return Err(AppError::InvalidArgument(format!(
+                "A conversion with id {} is already running",
+                job_id
+            )));
+        }
+        let handle = Arc::new(ConversionHandle {
+            child: Mutex::new(None),
+            cancelled: AtomicBool::new(false),
+        });
+        jobs.insert(job_id.to_string(), handle.clone());
+        Ok(handle)
+    }
+
+    pub fn remove(&self, job_id: &str) {
+        self.jobs
+            .lock()
+            .expect("conversion jobs poisoned")
+            .remove(job_id);
+    }
+
+    /// Kill a running conversion. Returns false if no such job is running.
+    pub fn cancel(&self, job_id: &str) -> bool {
+        let handle = self
+            .jobs
+            .lock()
+            .expect("conversion jobs poisoned")
+            .get(job_id)
+            .cloned();
+        match handle {
+            Some(handle) => {
+                handle.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn running_id_cannot_be_registered_twice() {
+        let jobs = ConversionJobs::default();
+        let first = jobs.register("song").unwrap();
+        assert!(matches!(
+            jobs.register("song"),
+            Err(AppError::InvalidArgument(_))
+        ));
+
+        // The first job stays cancellable
+        assert!(jobs.cancel("song"));
+        assert!(first.is_cancelled());
+
+        jobs.remove("song");
+        assert!(jobs.register("song").is_ok());
+    }
+}
@@ -0,0 +1,299 @@
+pub mod container;
+pub mod jobs;
+pub mod normalize;
+pub mod output;
+pub mod probe;
+pub mod profile;
+pub mod progress;
+
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use self::container::Container;
+use self::jobs::{ConversionHandle, ConversionJobs};
+use self::normalize::LoudnessTarget;
+use self::probe::FfmpegReport;
+use self::profile::ExportProfile;
+use self::progress::{parse_duration_line, ConversionProgress, ProgressParser};
+use crate::errors::{AppError, AppResult};
+
+/// Numbers two-pass statistics files within this run. Job ids come from the
+/// caller, so they are never part of a file name.
+static NEXT_PASS_LOG: AtomicU32 = AtomicU32::new(0);
+
+/// A `Command` for the FFmpeg binary found by `probe::locate`, preferring the
+/// user-configured path. Falls back to a bare `ffmpeg` so a missing binary
+/// still surfaces as `FfmpegMissing`.
+pub fn command(configured: Option<&Path>) -> Command {
+    match probe::locate(configured) {
+        Some(binary) => Command::new(binary.path),
+        None => Command::new("ffmpeg"),
+    }
+}
+
+/// Locate and probe FFmpeg. A missing binary is a report, not an error.
+pub fn report(configured: Option<&Path>) -> AppResult<FfmpegReport> {
+    match probe::locate(configured) {
+        Some(binary) => probe::probe(&binary),
+        None => Ok(FfmpegReport::default()),
+    }
+}
+
+/// Convert `input_path` to `output_path` using `profile`, reporting progress
+/// as FFmpeg runs. The job can be killed through `jobs` for as long as it
+/// runs, including between passes. The output path must already be resolved
+/// (see `output::resolve_output_path`).
+/// With `loudness`, the audio is measured first (in the `Measuring` phase,
+/// also cancellable) and normalized to it.
+#[allow(clippy::too_many_arguments)]
+pub fn convert(
+    ffmpeg_path: Option<&Path>,
+    input_path: &Path,
+    output_path: &Path,
+    profile: &ExportProfile,
+    loudness: Option<&LoudnessTarget>,
+    jobs: &ConversionJobs,
+    job_id: &str,
+    on_progress: impl FnMut(ConversionProgress),
+) -> AppResult<()> {
+    let handle = jobs.register(job_id)?;
+    let result = run_passes(
+        ffmpeg_path,
+        input_path,
+        output_path,
+        profile,
+        loudness,
+        &handle,
+        job_id,
+        on_progress,
+    );
+    jobs.remove(job_id);
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+fn run_passes(
+    ffmpeg_path: Option<&Path>,
+    input_path: &Path,
+    output_path: &Path,
+    profile: &ExportProfile,
+    loudness: Option<&LoudnessTarget>,
+    handle: &ConversionHandle,
+    job_id: &str,
+    mut on_progress: impl FnMut(ConversionProgress),
+) -> AppResult<()> {
+    let container = Container::from_path(output_path)?;
+    container.check(profile)?;
+    profile.validate()?;
+
+    let audio_filter = match loudness {
+        Some(target) if container != Container::Gif && profile.audio_encoder_name().is_some() => {
+            target.audio_filter_args(ffmpeg_path, input_path, handle, job_id, &mut on_progress)?
+        }
+        _ => Vec::new(),
+    };
+
+    let passes: u8 = if profile.two_pass && container != Container::Gif {
+        2
+    } else {
+        1
+    };
+    let pass_log = std::env::temp_dir().join(format!(
+        "music-visualizer-pass-{}-{}",
+        std::process::id(),
+        NEXT_PASS_LOG.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let mut result = Ok(());
+    for pass in 1..=passes {
+        let mut command = command(ffmpeg_path);
+        command
+            .args(["-hide_banner", "-nostats", "-progress", "pipe:1"])
+            .arg("-i")
+            .arg(input_path);
+        if container == Container::Gif {
+            command
+                .args(Container::gif_args())
+                .arg("-y")
+                .arg(output_path);
+        } else {
+            command.args(profile.video_args());
+            if passes > 1 {
+                command.args(profile.pass_args(pass, &pass_log));
+            }
+            if pass < passes {
+                // The analysis pass only needs the video stream and writes no file
+                command.args(["-an", "-f", "null", "-y", "-"]);
+            } else {
+                command
+                    .args(profile.audio_args())
+                    .args(&audio_filter)
+                    .arg("-y")
+                    .arg(output_path);
+            }
+        }
+
+        // Progress is reported over the whole job: pass 1 covers 0–50% of a two-pass encode
+        result = run_with_progress(command, handle, job_id, |mut progress| {
+            progress.percent = progress
+                .percent
+                .map(|percent| (f64::from(pass - 1) * 100.0 + percent) / f64::from(passes));
+            progress.done = progress.done && pass == passes;
+            on_progress(progress);
+        })
+        .map(drop);
+        if result.is_err() {
+            break;
+        }
+    }
+
+    if passes > 1 {
+        remove_pass_logs(&pass_log);
+    }
+    if let Err(error) = result {
+        if error == AppError::Cancelled {
+            let _ = std::fs::remove_file(output_path);
+        }
+        return Err(error);
+    }
+    Ok(())
+}
+
+/// Run one FFmpeg invocation that writes `-progress` to stdout, unless the
+/// job has already been cancelled. Returns what FFmpeg printed on stderr.
+fn run_with_progress(
+    mut command: Command,
+    handle: &ConversionHandle,
+    job_id: &str,
+    mut on_progress: impl FnMut(ConversionProgress),
+) -> AppResult<String> {
+    if handle.is_cancelled() {
+        return Err(AppError::Cancelled);
+    }
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(AppError::ffmpeg_spawn)?;
+
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+    let started = Instant::now();
+
+    // stderr carries the input duration (printed before any progress) and the error text
+    let duration = Arc::new(Mutex::new(None::<f64>));
+    let stderr_thread = {
+        let duration = duration.clone();
+        std::thread::spawn(move || {
+            let mut text = String::new();
+            let mut reader = BufReader::new(stderr);
+            let mut line = Vec::new();
+            while reader.read_until(b'\n', &mut line).unwrap_or(0) > 0 {
+                let decoded = String::from_utf8_lossy(&line);
+                if let Some(seconds) = parse_duration_line(&decoded) {
+                    duration
+                        .lock()
+                        .expect("duration poisoned")
+                        .get_or_insert(seconds);
+                }
+                text.push_str(&decoded);
+                line.clear();
+            }
+            text
+        })
+    };
+
+    handle.attach(child);
+
+    let mut parser = ProgressParser::default();
+    for line in BufReader::new(stdout).lines() {
+        let Ok(line) = line else { break };
+        if let Some(snapshot) = parser.push_line(&line) {
+            let duration = *duration.lock().expect("duration poisoned");
+            on_progress(snapshot.into_progress(job_id, duration, started.elapsed()));
+        }
+    }
+
+    let status = handle.wait();
+    let stderr = stderr_thread.join().unwrap_or_default();
+
+    if handle.is_cancelled() {
+        return Err(AppError::Cancelled);
+    }
+    let status =
+        status.map_err(|e| AppError::Internal(format!("Failed to wait for FFmpeg: {}", e)))?;
+
+    if status.success() {
+        Ok(stderr)
+    } else {
+        Err(AppError::ffmpeg_failed(status, &stderr))
+    }
+}
+
+/// Files a two-pass encode writes for `-passlogfile <prefix>` (x264, VP9,
+/// AV1) or `-x265-params stats=<prefix>.log`, including the `.temp` copies
+/// the encoders rename into place.
+const PASS_LOG_SUFFIXES: [&str; 8] = [
+    "-0.log",
+    "-0.log.temp",
+    "-0.log.mbtree",
+    "-0.log.mbtree.temp",
+    ".log",
+    ".log.temp",
+    ".log.cutree",
+    ".log.cutree.temp",
+];
+
+/// Delete the statistics files a two-pass encode leaves behind.
+fn remove_pass_logs(prefix: &Path) {
+    for suffix in PASS_LOG_SUFFIXES {
+        let mut path = prefix.as_os_str().to_owned();
+        path.push(suffix);
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Read everything from a pipe on a background thread.
+pub fn drain(mut pipe: impl Read + Send + 'static) -> std::thread::JoinHandle<String> {
+    std::thread::spawn(move || {
+        let mut output = String::new();
+        let _ = pipe.read_to_string(&mut output);
+        output
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pass_log_cleanup_leaves_other_files() {
+        let dir =
+            std::env::temp_dir().join(format!("music-visualizer-passlogs-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let own = [
+            "job-1-0.log",
+            "job-1-0.log.mbtree",
+            "job-1.log",
+            "job-1.log.cutree",
+        ];
+        let others = ["job-10-0.log", "job-1-song.wav", "job-1"];
+        for name in own.iter().chain(&others) {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+
+        remove_pass_logs(&dir.join("job-1"));
+        let remaining: Vec<bool> = own
+            .iter()
+            .chain(&others)
+            .map(|name| dir.join(name).exists())
+            .collect();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(remaining, [false, false, false, false, true, true, true]);
+    }
+}
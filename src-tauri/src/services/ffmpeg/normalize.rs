@@ -0,0 +1,337 @@
+use std::path::Path;
+use std::process::Command;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use serde::Deserialize;
+
+use super::jobs::ConversionHandle;
+use super::progress::{ConversionPhase, ConversionProgress};
+use crate::errors::{AppError, AppResult};
+use crate::services::analyzer::loudness::{self, LoudnessAnalysis};
+use crate::services::decoder;
+
+/// Numbers the temporary WAV files loudness is measured from within this run.
+static NEXT_MEASUREMENT: AtomicU32 = AtomicU32::new(0);
+
+/// How the soundtrack is brought to the target loudness.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NormalizationMethod {
+    /// Measure in Rust (EBU R128) on audio decoded by FFmpeg and apply one
+    /// constant gain, lowered where needed so the true peak stays under the
+    /// ceiling.
+    #[default]
+    Gain,
+    /// FFmpeg's two-pass `loudnorm`: a measuring pass, then a linear pass with
+    /// the measured values (falling back to dynamic mode when it must).
+    Loudnorm,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LoudnessTarget {
+    /// Integrated loudness in LUFS.
+    pub integrated: f64,
+    /// True-peak ceiling in dBTP.
+    pub true_peak: f64,
+    /// Target loudness range in LU (`loudnorm` only).
+    pub loudness_range: f64,
+    pub method: NormalizationMethod,
+}
+
+impl Default for LoudnessTarget {
+    fn default() -> Self {
+        LoudnessTarget {
+            integrated: -14.0,
+            true_peak: -1.0,
+            loudness_range: 11.0,
+            method: NormalizationMethod::Gain,
+        }
+    }
+}
+
+/// The first-pass report `loudnorm` prints with `print_format=json`.
+#[derive(Deserialize)]
+struct LoudnormMeasurement {
+    input_i: String,
+    input_tp: String,
+    input_lra: String,
+    input_thresh: String,
+    target_offset: String,
+}
+
+impl LoudnessTarget {
+    fn validate(&self) -> AppResult<()> {
+        if !(-70.0..=-5.0).contains(&self.integrated) {
+            return Err(AppError::InvalidArgument(format!(
+                "Target loudness must be between -70 and -5 LUFS, got {}",
+                self.integrated
+            )));
+        }
+        if !(-9.0..=0.0).contains(&self.true_peak) {
+            return Err(AppError::InvalidArgument(format!(
+                "True-peak ceiling must be between -9 and 0 dBTP, got {}",
+                self.true_peak
+            )));
+        }
+        if !(1.0..=50.0).contains(&self.loudness_range) {
+            return Err(AppError::InvalidArgument(format!(
+                "Target loudness range must be between 1 and 50 LU, got {}",
+                self.loudness_range
+            )));
+        }
+        Ok(())
+    }
+
+    /// `-af` arguments that normalize the audio of `input` (a sound file, or a
+    /// video whose first audio stream is used). Measuring decodes the whole
+    /// audio through FFmpeg as a pass of the job behind `handle`, reported in
+    /// the `Measuring` phase. Silent input is left as it is.
+    pub fn audio_filter_args(
+        &self,
+        ffmpeg_path: Option<&Path>,
+        input: &Path,
+        handle: &ConversionHandle,
+        job_id: &str,
+        mut on_progress: impl FnMut(ConversionProgress),
+    ) -> AppResult<Vec<String>> {
+        self.validate()?;
+        let measurement = Measurement {
+            ffmpeg_path,
+            input,
+            handle,
+            job_id,
+            on_progress: &mut on_progress,
+        };
+        let filter = match self.method {
+            NormalizationMethod::Gain => {
+                let measured = measure_with_ffmpeg(measurement)?;
+                self.gain(&measured)
+                    .map(|gain| format!("volume={:.2}dB", gain))
+            }
+            NormalizationMethod::Loudnorm => self.loudnorm_filter(measurement)?,
+        };
+        if handle.is_cancelled() {
+            return Err(AppError::Cancelled);
+        }
+        Ok(filter
+            .map(|filter| vec!["-af".to_string(), filter])
+            .unwrap_or_default())
+    }
+
+    /// Gain in dB that brings `measured` to the target loudness, lowered so the
+    /// true peak stays under the ceiling. `None` for silence.
+    fn gain(&self, measured: &LoudnessAnalysis) -> Option<f64> {
+        let (integrated, true_peak) = (measured.integrated?, measured.true_peak?);
+        Some((self.integrated - integrated).min(self.true_peak - true_peak))
+    }
+
+    fn loudnorm_filter(&self, measurement: Measurement) -> AppResult<Option<String>> {
+        let target = format!(
+            "I={}:TP={}:LRA={}",
+            self.integrated, self.true_peak, self.loudness_range
+        );
+        let stderr = measurement.run(|command| {
+            command
+                .args(["-vn", "-af"])
+                .arg(format!("loudnorm={}:print_format=json", target))
+                .args(["-f", "null", "-"]);
+        })?;
+
+        // The report is the last JSON object on stderr
+        let report = stderr
+            .rfind('{')
+            .and_then(|start| {
+                stderr[start..]
+                    .find('}')
+                    .map(|end| &stderr[start..=start + end])
+            })
+            .ok_or_else(|| {
+                AppError::Internal("FFmpeg did not print a loudnorm measurement".to_string())
+            })?;
+        let measured: LoudnormMeasurement = serde_json::from_str(report)
+            .map_err(|e| AppError::Internal(format!("Unreadable loudnorm measurement: {}", e)))?;
+        // Silence measures as -inf, which cannot be normalized
+        if measured
+            .input_i
+            .parse::<f64>()
+            .map_or(true, |i| !i.is_finite())
+        {
+            return Ok(None);
+        }
+
+        // loudnorm runs at 192 kHz internally; bring it back to a rate every encoder takes
+        Ok(Some(format!(
+            "loudnorm={}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true,aresample=48000",
+            target,
+            measured.input_i,
+            measured.input_tp,
+            measured.input_lra,
+            measured.input_thresh,
+            measured.target_offset
+        )))
+    }
+}
+
+/// An FFmpeg pass that reads the audio of `input` for a job.
+struct Measurement<'a> {
+    ffmpeg_path: Option<&'a Path>,
+    input: &'a Path,
+    handle: &'a ConversionHandle,
+    job_id: &'a str,
+    on_progress: &'a mut dyn FnMut(ConversionProgress),
+}
+
+impl Measurement<'_> {
+    /// Run FFmpeg on the input with the output arguments `output` adds, and
+    /// return its stderr.
+    fn run(self, output: impl FnOnce(&mut Command)) -> AppResult<String> {
+        let mut command = super::command(self.ffmpeg_path);
+        command
+            .args(["-hide_banner", "-nostats", "-progress", "pipe:1", "-i"])
+            .arg(self.input);
+        output(&mut command);
+        let on_progress = self.on_progress;
+        super::run_with_progress(command, self.handle, self.job_id, |mut progress| {
+            progress.phase = ConversionPhase::Measuring;
+            // The encode is still to come
+            progress.done = false;
+            on_progress(progress);
+        })
+    }
+}
+
+/// Loudness of the first audio stream of the input. FFmpeg decodes it to a
+/// temporary float WAV first, so any container it reads works, including the
+/// WebM/Opus that MediaRecorder produces.
+fn measure_with_ffmpeg(measurement: Measurement) -> AppResult<LoudnessAnalysis> {
+    let wav = std::env::temp_dir().join(format!(
+        "music-visualizer-loudness-{}-{}.wav",
+        std::process::id(),
+        NEXT_MEASUREMENT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let handle = measurement.handle;
+    let measured = measurement
+        .run(|command| {
+            command
+                .args(["-vn", "-map", "0:a:0", "-c:a", "pcm_f32le", "-y"])
+                .arg(&wav);
+        })
+        .and_then(|_| {
+            if handle.is_cancelled() {
+                return Err(AppError::Cancelled);
+            }
+            decoder::decode_file(&wav)
+        })
+        .and_then(|audio| loudness::measure(&audio.channels, audio.sample_rate));
+    let _ = std::fs::remove_file(&wav);
+    measured
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::ffmpeg::jobs::ConversionJobs;
+    use crate::services::ffmpeg::probe;
+
+    fn measured(integrated: Option<f64>, true_peak: Option<f64>) -> LoudnessAnalysis {
+        LoudnessAnalysis {
+            integrated,
+            loudness_range: 0.0,
+            true_peak,
+            sample_peak: true_peak,
+            interval: 0.1,
+            momentary: Vec::new(),
+            short_term: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn gain_reaches_the_target_under_the_ceiling() {
+        let target = LoudnessTarget::default();
+        // -20 LUFS with plenty of headroom: +6 dB to reach -14
+        assert_eq!(target.gain(&measured(Some(-20.0), Some(-12.0))), Some(6.0));
+        // Peaks at -4 dBTP: only +3 dB before hitting the -1 dBTP ceiling
+        assert_eq!(target.gain(&measured(Some(-20.0), Some(-4.0))), Some(3.0));
+        // Too loud: turned down
+        assert_eq!(target.gain(&measured(Some(-8.0), Some(0.5))), Some(-6.0));
+        assert_eq!(target.gain(&measured(None, None)), None);
+    }
+
+    #[test]
+    fn gain_of_a_measured_sine() {
+        // A -20 dBFS 1 kHz sine in both channels measures about -20 LUFS
+        // (the K-weighting gain at 1 kHz and the stereo sum nearly cancel)
+        let sine: Vec<f32> = (0..48_000 * 5)
+            .map(|n| 0.1 * (2.0 * std::f32::consts::PI * 1000.0 * n as f32 / 48_000.0).sin())
+            .collect();
+        let analysis = loudness::measure(&[sine.clone(), sine], 48_000).unwrap();
+        let target = LoudnessTarget {
+            integrated: -23.0,
+            ..LoudnessTarget::default()
+        };
+        let gain = target.gain(&analysis).unwrap();
+        assert!((gain + 3.0).abs() < 0.3, "{}", gain);
+    }
+
+    #[test]
+    #[ignore = "needs FFmpeg"]
+    fn gain_is_measured_through_ffmpeg() {
+        let ffmpeg = probe::locate(None).expect("FFmpeg not found");
+        // AC-3, which FFmpeg always encodes and symphonia cannot read
+        let input = std::env::temp_dir().join(format!(
+            "music-visualizer-normalize-{}.mka",
+            std::process::id()
+        ));
+        let sine = "0.1*sin(2*PI*1000*t)";
+        let status = std::process::Command::new(&ffmpeg.path)
+            .args(["-hide_banner", "-loglevel", "error", "-f", "lavfi", "-i"])
+            .arg(format!("aevalsrc={}|{}:s=48000:d=5", sine, sine))
+            .args(["-c:a", "ac3", "-b:a", "192k", "-y"])
+            .arg(&input)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let target = LoudnessTarget {
+            integrated: -23.0,
+            ..LoudnessTarget::default()
+        };
+        let jobs = ConversionJobs::default();
+        let handle = jobs.register("measure").unwrap();
+        let mut phases = Vec::new();
+        let args =
+            target.audio_filter_args(Some(&ffmpeg.path), &input, &handle, "measure", |progress| {
+                phases.push(progress.phase)
+            });
+        let _ = std::fs::remove_file(&input);
+        let args = args.unwrap();
+        assert_eq!(args[0], "-af");
+        let gain: f64 = args[1]
+            .strip_prefix("volume=")
+            .and_then(|gain| gain.strip_suffix("dB"))
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!((gain + 3.0).abs() < 0.3, "{}", args[1]);
+        assert!(!phases.is_empty());
+        assert!(phases
+            .iter()
+            .all(|phase| *phase == ConversionPhase::Measuring));
+    }
+
+    #[test]
+    fn cancelled_job_is_not_measured() {
+        let jobs = ConversionJobs::default();
+        let handle = jobs.register("measure").unwrap();
+        jobs.cancel("measure");
+        let result = LoudnessTarget::default().audio_filter_args(
+            Some(Path::new("/nonexistent/ffmpeg")),
+            Path::new("/nonexistent/song.mka"),
+            &handle,
+            "measure",
+            |_| {},
+        );
+        assert_eq!(result, Err(AppError::Cancelled));
+    }
+}
@@ -0,0 +1,195 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::errors::{AppError, AppResult};
+
+/// What to do when the output file already exists.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExistingFilePolicy {
+    Overwrite,
+    /// Pick `name (1).ext`, `name (2).ext`, ... instead.
+    #[default]
+    Unique,
+    Fail,
+}
+
+/// True if both paths name the same file. Canonicalizing also catches
+/// case-only differences on case-insensitive file systems.
+pub fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// Decide where a conversion of `input` writes: `explicit` if given, otherwise
+/// `input` with its extension replaced by `format`. Never returns `input` itself.
+pub fn resolve_output_path(
+    input: &Path,
+    explicit: Option<&Path>,
+    format: &str,
+    policy: ExistingFilePolicy,
+) -> AppResult<PathBuf> {
+    let candidate = match explicit {
+        Some(path) => path.to_path_buf(),
+        None => input.with_extension(format),
+    };
+
+    let clobbers_input = same_file(input, &candidate);
+    if !clobbers_input && !candidate.exists() {
+        return Ok(candidate);
+    }
+
+    match policy {
+        ExistingFilePolicy::Unique => Ok(unique_path(&candidate)),
+        _ if clobbers_input => Err(AppError::InvalidArgument(format!(
+            "Output path {} would overwrite the input file",
+            candidate.display()
+        ))),
+        ExistingFilePolicy::Overwrite => Ok(candidate),
+        ExistingFilePolicy::Fail => Err(AppError::Io {
+            path: Some(candidate.to_string_lossy().into_owned()),
+            message: format!("{} already exists", candidate.display()),
+        }),
+    }
+}
+
+/// First `stem (n).ext` next to `path` that does not exist yet.
+fn unique_path(path: &Path) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path.extension().map(|e| e.to_string_lossy().into_owned());
+    (1u32..)
+        .map(|n| {
+            let name = match &extension {
+                Some(extension) => format!("{} ({}).{}", stem, n, extension),
+                None => format!("{} ({})", stem, n),
+            };
+            path.with_file_name(name)
+        })
+        .find(|candidate| !candidate.exists())
+        .expect("an unused file name exists")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory of its own for each test, removed on drop.
+    struct Scratch(PathBuf);
+
+    impl Scratch {
+        fn new(name: &str) -> Scratch {
+            let dir = std::env::temp_dir().join(format!(
+                "music-visualizer-output-{}-{}",
+                std::process::id(),
+                name
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            Scratch(dir)
+        }
+
+        fn touch(&self, name: &str) -> PathBuf {
+            let path = self.0.join(name);
+            fs::write(&path, b"").unwrap();
+            path
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn only_the_file_extension_is_replaced() {
+        let scratch = Scratch::new("dir-extension");
+        fs::create_dir(scratch.0.join("take.webm")).unwrap();
+        let input = scratch.touch("take.webm/clip.webm");
+
+        let output = resolve_output_path(&input, None, "mp4", ExistingFilePolicy::Unique).unwrap();
+        assert_eq!(output, scratch.0.join("take.webm").join("clip.mp4"));
+    }
+
+    #[test]
+    fn uppercase_input_is_never_the_output() {
+        let scratch = Scratch::new("uppercase");
+        let input = scratch.touch("clip.WEBM");
+
+        for policy in [ExistingFilePolicy::Unique, ExistingFilePolicy::Overwrite] {
+            match resolve_output_path(&input, None, "webm", policy) {
+                Ok(output) => {
+                    assert!(!same_file(&input, &output));
+                    assert_eq!(output.extension().unwrap(), "webm");
+                }
+                // A case-insensitive file system sees the input itself
+                Err(error) => assert!(matches!(error, AppError::InvalidArgument(_))),
+            }
+        }
+    }
+
+    #[test]
+    fn same_extension_refuses_to_clobber_the_input() {
+        let scratch = Scratch::new("same-extension");
+        let input = scratch.touch("clip.mkv");
+
+        for policy in [ExistingFilePolicy::Overwrite, ExistingFilePolicy::Fail] {
+            assert!(matches!(
+                resolve_output_path(&input, None, "mkv", policy),
+                Err(AppError::InvalidArgument(_))
+            ));
+            assert!(matches!(
+                resolve_output_path(&input, Some(&input), "mkv", policy),
+                Err(AppError::InvalidArgument(_))
+            ));
+        }
+        assert_eq!(
+            resolve_output_path(&input, None, "mkv", ExistingFilePolicy::Unique).unwrap(),
+            scratch.0.join("clip (1).mkv")
+        );
+    }
+
+    #[test]
+    fn existing_output_follows_the_policy() {
+        let scratch = Scratch::new("existing");
+        let input = scratch.touch("clip.webm");
+        let existing = scratch.touch("clip.mp4");
+
+        assert_eq!(
+            resolve_output_path(&input, None, "mp4", ExistingFilePolicy::Unique).unwrap(),
+            scratch.0.join("clip (1).mp4")
+        );
+        scratch.touch("clip (1).mp4");
+        assert_eq!(
+            resolve_output_path(&input, None, "mp4", ExistingFilePolicy::Unique).unwrap(),
+            scratch.0.join("clip (2).mp4")
+        );
+        assert_eq!(
+            resolve_output_path(&input, None, "mp4", ExistingFilePolicy::Overwrite).unwrap(),
+            existing
+        );
+        assert!(matches!(
+            resolve_output_path(&input, None, "mp4", ExistingFilePolicy::Fail),
+            Err(AppError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn missing_output_is_used_as_is() {
+        let scratch = Scratch::new("missing");
+        let input = scratch.touch("clip.webm");
+        let explicit = scratch.0.join("render.mov");
+
+        assert_eq!(
+            resolve_output_path(&input, Some(&explicit), "mov", ExistingFilePolicy::Fail).unwrap(),
+            explicit
+        );
+    }
+}
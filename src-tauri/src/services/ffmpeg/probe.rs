@@ -0,0 +1,304 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde::Serialize;
+
+use super::container::Container;
+use crate::errors::{AppError, AppResult};
+
+const BINARY_NAME: &str = "ffmpeg";
+
+/// Where the FFmpeg binary was found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BinarySource {
+    /// A path chosen by the user.
+    Configured,
+    /// Bundled next to the app executable (Tauri `externalBin`).
+    Sidecar,
+    /// Found on `PATH`.
+    Path,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FfmpegBinary {
+    pub path: PathBuf,
+    pub source: BinarySource,
+}
+
+/// What `check_ffmpeg_installed` reports about the FFmpeg the app would use.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FfmpegReport {
+    pub installed: bool,
+    pub path: Option<String>,
+    pub source: Option<BinarySource>,
+    /// As printed by `ffmpeg -version`, e.g. `6.1.1` or `N-113000-g1234abcd`.
+    pub version: Option<String>,
+    pub encoders: Vec<String>,
+    pub muxers: Vec<String>,
+    pub hwaccels: Vec<String>,
+    /// Output formats (file extensions) this build can produce.
+    pub formats: Vec<String>,
+}
+
+fn executable_name() -> String {
+    format!("{}{}", BINARY_NAME, std::env::consts::EXE_SUFFIX)
+}
+
+/// Find FFmpeg: the configured path first, then a bundled sidecar, then `PATH`.
+pub fn locate(configured: Option<&Path>) -> Option<FfmpegBinary> {
+    if let Some(path) = configured.filter(|p| p.is_file()) {
+        return Some(FfmpegBinary {
+            path: path.to_path_buf(),
+            source: BinarySource::Configured,
+        });
+    }
+
+    let sidecar = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.join(executable_name())))
+        .filter(|p| p.is_file());
+    if let Some(path) = sidecar {
+        return Some(FfmpegBinary {
+            path,
+            source: BinarySource::Sidecar,
+        });
+    }
+
+    let search_path = std::env::var_os("PATH")?;
+    std::env::split_paths(&search_path)
+        .map(|dir| dir.join(executable_name()))
+        .find(|p| p.is_file())
+        .map(|path| FfmpegBinary {
+            path,
+            source: BinarySource::Path,
+        })
+}
+
+/// Run the binary and collect its version and capabilities.
+pub fn probe(binary: &FfmpegBinary) -> AppResult<FfmpegReport> {
+    let version_output = run(&binary.path, &["-version"])?;
+    let encoders = parse_table(&run(&binary.path, &["-hide_banner", "-encoders"])?);
+    let muxers = parse_table(&run(&binary.path, &["-hide_banner", "-muxers"])?);
+    let hwaccels = parse_hwaccels(&run(&binary.path, &["-hide_banner", "-hwaccels"])?);
+    let formats = producible_formats(&encoders, &muxers);
+
+    Ok(FfmpegReport {
+        installed: true,
+        path: Some(binary.path.to_string_lossy().into_owned()),
+        source: Some(binary.source),
+        version: parse_version(&version_output),
+        encoders,
+        muxers,
+        hwaccels,
+        formats,
+    })
+}
+
+fn run(binary: &Path, args: &[&str]) -> AppResult<String> {
+    let output = Command::new(binary)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(AppError::ffmpeg_spawn)?;
+    if !output.status.success() {
+        return Err(AppError::ffmpeg_failed(
+            output.status,
+            &String::from_utf8_lossy(&output.stderr),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// `ffmpeg version 6.1.1-3ubuntu5 Copyright ...` -> `6.1.1-3ubuntu5`
+pub fn parse_version(output: &str) -> Option<String> {
+    output
+        .lines()
+        .next()?
+        .strip_prefix("ffmpeg version ")?
+        .split_whitespace()
+        .next()
+        .map(str::to_string)
+}
+
+/// Names from the `-encoders` / `-muxers` listings: every row after the
+/// dashed separator is `<flags> <name> <description>`.
+pub fn parse_table(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .skip_while(|line| {
+            let line = line.trim();
+            line.is_empty() || !line.chars().all(|c| c == '-')
+        })
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .flat_map(|names| names.split(','))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Names listed under `Hardware acceleration methods:`.
+pub fn parse_hwaccels(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .skip_while(|line| !line.trim_end().ends_with(':'))
+        .skip(1)
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// A format is producible when its muxer exists and at least one video codec
+/// it accepts has an encoder (GIF needs the `gif` encoder).
+fn producible_formats(encoders: &[String], muxers: &[String]) -> Vec<String> {
+    let has = |list: &[String], name: &str| list.iter().any(|n| n == name);
+    Container::ALL
+        .iter()
+        .filter(|container| has(muxers, container.muxer_name()))
+        .filter(|container| match container {
+            Container::Gif => has(encoders, "gif"),
+            _ => container
+                .video_codecs()
+                .iter()
+                .any(|codec| has(encoders, codec.encoder_name())),
+        })
+        .map(|container| container.name().to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERSION: &str = "\
+ffmpeg version 6.1.1-3ubuntu5 Copyright (c) 2000-2023 the FFmpeg developers
+built with gcc 13 (Ubuntu 13.2.0-23ubuntu3)
+configuration: --prefix=/usr --extra-version=3ubuntu5 --toolchain=hardened --enable-gpl --enable-libx264
+libavutil      58. 29.100 / 58. 29.100
+libavcodec     60. 31.102 / 60. 31.102
+";
+
+    const GIT_VERSION: &str = "\
+ffmpeg version N-113445-g9b27e0fd6c-20240126 Copyright (c) 2000-2024 the FFmpeg developers
+built with gcc 13.2.0 (crosstool-NG 1.25.0.232_c175b21)
+";
+
+    /// `ffmpeg -hide_banner -encoders`, cut down to a few rows per kind.
+    const ENCODERS: &str = "\
+Encoders:
+ V..... = Video
+ A..... = Audio
+ S..... = Subtitle
+ .F.... = Frame-level multithreading
+ ..S... = Slice-level multithreading
+ ...X.. = Codec is experimental
+ ....B. = Supports draw_horiz_band
+ .....D = Supports direct rendering method 1
+ ------
+ V....D a64multi             Multicolor charset for Commodore 64 (codec a64_multi)
+ V....D gif                  GIF (Graphics Interchange Format)
+ V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10 (codec h264)
+ V....D libx264rgb           libx264 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10 RGB (codec h264)
+ VF..B. prores_ks            Apple ProRes (iCodec Pro) (codec prores)
+ A....D aac                  AAC (Advanced Audio Coding)
+ A....D flac                 FLAC (Free Lossless Audio Codec)
+ A....D libopus              libopus Opus (codec opus)
+ A....D pcm_s24le            PCM signed 24-bit little-endian
+ S..... ass                  ASS (Advanced SubStation Alpha) subtitle
+";
+
+    /// `ffmpeg -hide_banner -muxers`, cut down.
+    const MUXERS: &str = "\
+ Formats:
+ D. = Demuxing supported
+ .E = Muxing supported
+ --
+  E 3g2             3GP2 (3GPP2 file format)
+  E gif             CompuServe Graphics Interchange Format (GIF)
+  E matroska        Matroska
+  E mov             QuickTime / MOV
+  E mp4             MP4 (MPEG-4 Part 14)
+  E webm            WebM
+";
+
+    const HWACCELS: &str = "\
+Hardware acceleration methods:
+vdpau
+cuda
+vaapi
+qsv
+drm
+opencl
+vulkan
+
+";
+
+    #[test]
+    fn release_version() {
+        assert_eq!(parse_version(VERSION).as_deref(), Some("6.1.1-3ubuntu5"));
+    }
+
+    #[test]
+    fn git_build_version() {
+        assert_eq!(
+            parse_version(GIT_VERSION).as_deref(),
+            Some("N-113445-g9b27e0fd6c-20240126")
+        );
+    }
+
+    #[test]
+    fn version_of_something_else() {
+        assert_eq!(
+            parse_version("avconv version 12.3, Copyright (c) 2000-2018"),
+            None
+        );
+        assert_eq!(parse_version(""), None);
+    }
+
+    #[test]
+    fn encoder_rows_skip_the_legend() {
+        assert_eq!(
+            parse_table(ENCODERS),
+            [
+                "a64multi",
+                "gif",
+                "libx264",
+                "libx264rgb",
+                "prores_ks",
+                "aac",
+                "flac",
+                "libopus",
+                "pcm_s24le",
+                "ass"
+            ]
+        );
+    }
+
+    #[test]
+    fn muxer_rows_skip_the_legend() {
+        assert_eq!(
+            parse_table(MUXERS),
+            ["3g2", "gif", "matroska", "mov", "mp4", "webm"]
+        );
+    }
+
+    #[test]
+    fn hwaccels_after_the_heading() {
+        assert_eq!(
+            parse_hwaccels(HWACCELS),
+            ["vdpau", "cuda", "vaapi", "qsv", "drm", "opencl", "vulkan"]
+        );
+        assert!(parse_hwaccels("Hardware acceleration methods:\n\n").is_empty());
+    }
+
+    #[test]
+    fn formats_need_a_muxer_and_a_video_encoder() {
+        // No libvpx-vp9 or libaom-av1 in this build, so no WebM
+        assert_eq!(
+            producible_formats(&parse_table(ENCODERS), &parse_table(MUXERS)),
+            ["mp4", "mov", "mkv", "gif"]
+        );
+    }
+}
@@ -0,0 +1,382 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use super::container::Container;
+use crate::errors::{AppError, AppResult};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoCodec {
+    X264,
+    X265,
+    Vp9,
+    Av1,
+    ProRes,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioCodec {
+    Aac,
+    Opus,
+    Pcm,
+    Flac,
+    None,
+}
+
+impl VideoCodec {
+    /// The FFmpeg encoder used for this codec.
+    pub fn encoder_name(self) -> &'static str {
+        match self {
+            VideoCodec::X264 => "libx264",
+            VideoCodec::X265 => "libx265",
+            VideoCodec::Vp9 => "libvpx-vp9",
+            VideoCodec::Av1 => "libaom-av1",
+            VideoCodec::ProRes => "prores_ks",
+        }
+    }
+}
+
+impl AudioCodec {
+    pub fn encoder_name(self) -> Option<&'static str> {
+        match self {
+            AudioCodec::Aac => Some("aac"),
+            AudioCodec::Opus => Some("libopus"),
+            AudioCodec::Pcm => Some("pcm_s24le"),
+            AudioCodec::Flac => Some("flac"),
+            AudioCodec::None => None,
+        }
+    }
+}
+
+/// Constant quality (`crf`) or average bitrate in kbit/s.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum RateControl {
+    Crf { value: u8 },
+    Bitrate { kbps: u32 },
+}
+
+/// ProRes flavours, in `prores_ks` profile order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProResProfile {
+    Proxy = 0,
+    Lt = 1,
+    Standard = 2,
+    Hq = 3,
+    #[serde(rename = "4444")]
+    P4444 = 4,
+    #[serde(rename = "4444xq")]
+    P4444Xq = 5,
+}
+
+/// How a video is encoded by `convert_video` and the offline renderer.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportProfile {
+    pub video_codec: VideoCodec,
+    /// Ignored for ProRes, which is sized by `prores_profile`.
+    pub rate_control: RateControl,
+    /// Encoder speed preset (x264/x265 names such as `medium`, `slow`).
+    #[serde(default)]
+    pub preset: Option<String>,
+    /// Defaults to `yuv420p`, or 10-bit 4:2:2 / 4:4:4 for ProRes.
+    #[serde(default)]
+    pub pixel_format: Option<String>,
+    #[serde(default)]
+    pub prores_profile: Option<ProResProfile>,
+    pub audio_codec: AudioCodec,
+    #[serde(default)]
+    pub audio_bitrate_kbps: Option<u32>,
+    #[serde(default)]
+    pub two_pass: bool,
+}
+
+/// The composer's `globalSettings.quality` values, plus a ProRes master.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QualityPreset {
+    Low,
+    Medium,
+    High,
+    Ultra,
+    Master,
+}
+
+/// A preset name or a fully specified profile, as sent by the frontend.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ProfileSpec {
+    Preset(QualityPreset),
+    Custom(ExportProfile),
+}
+
+impl QualityPreset {
+    /// Resolve the preset for a container: WebM gets VP9/Opus, everything else
+    /// H.264/AAC. `master` is ProRes only where the container can hold it.
+    pub fn profile(self, output_path: &Path) -> ExportProfile {
+        let container = Container::from_path(output_path).ok();
+        let webm = container == Some(Container::Webm);
+        let (x264_crf, vp9_crf, preset, audio_kbps) = match self {
+            QualityPreset::Low => (28, 38, "fast", 128),
+            QualityPreset::Medium => (23, 32, "medium", 192),
+            QualityPreset::High => (20, 28, "slow", 256),
+            QualityPreset::Ultra | QualityPreset::Master => (16, 24, "slow", 320),
+        };
+
+        if self == QualityPreset::Master
+            && matches!(container, Some(Container::Mov | Container::Mkv))
+        {
+            return ExportProfile {
+                video_codec: VideoCodec::ProRes,
+                rate_control: RateControl::Crf { value: 0 },
+                preset: None,
+                pixel_format: None,
+                prores_profile: Some(ProResProfile::P4444Xq),
+                audio_codec: AudioCodec::Pcm,
+                audio_bitrate_kbps: None,
+                two_pass: false,
+            };
+        }
+
+        ExportProfile {
+            video_codec: if webm {
+                VideoCodec::Vp9
+            } else {
+                VideoCodec::X264
+            },
+            rate_control: RateControl::Crf {
+                value: if webm { vp9_crf } else { x264_crf },
+            },
+            preset: (!webm).then(|| preset.to_string()),
+            pixel_format: None,
+            prores_profile: None,
+            audio_codec: if webm {
+                AudioCodec::Opus
+            } else {
+                AudioCodec::Aac
+            },
+            audio_bitrate_kbps: Some(audio_kbps),
+            two_pass: false,
+        }
+    }
+}
+
+impl ProfileSpec {
+    pub fn resolve(self, output_path: &Path) -> ExportProfile {
+        match self {
+            ProfileSpec::Preset(preset) => preset.profile(output_path),
+            ProfileSpec::Custom(profile) => profile,
+        }
+    }
+}
+
+/// Resolve an optional profile from a command, defaulting to the `medium` preset,
+/// and check that it can be written to the container of `output_path`.
+pub fn resolve_profile(spec: Option<ProfileSpec>, output_path: &Path) -> AppResult<ExportProfile> {
+    let profile = spec
+        .unwrap_or(ProfileSpec::Preset(QualityPreset::Medium))
+        .resolve(output_path);
+    profile.validate()?;
+    Container::from_path(output_path)?.check(&profile)?;
+    Ok(profile)
+}
+
+pub fn output_extension(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+}
+
+impl ExportProfile {
+    pub fn encoder_name(&self) -> &'static str {
+        self.video_codec.encoder_name()
+    }
+
+    pub fn audio_encoder_name(&self) -> Option<&'static str> {
+        self.audio_codec.encoder_name()
+    }
+
+    pub fn validate(&self) -> AppResult<()> {
+        if self.two_pass && self.video_codec == VideoCodec::ProRes {
+            return Err(AppError::InvalidArgument(
+                "ProRes does not support two-pass encoding".to_string(),
+            ));
+        }
+        if self.two_pass && !matches!(self.rate_control, RateControl::Bitrate { .. }) {
+            return Err(AppError::InvalidArgument(
+                "Two-pass encoding needs a target bitrate".to_string(),
+            ));
+        }
+        if let RateControl::Bitrate { kbps: 0 } = self.rate_control {
+            return Err(AppError::InvalidArgument(
+                "Video bitrate must be positive".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn default_pixel_format(&self) -> &'static str {
+        match (self.video_codec, self.prores_profile) {
+            (VideoCodec::ProRes, Some(ProResProfile::P4444 | ProResProfile::P4444Xq)) => {
+                "yuva444p10le"
+            }
+            (VideoCodec::ProRes, _) => "yuv422p10le",
+            _ => "yuv420p",
+        }
+    }
+
+    /// `-c:v ...` and everything that shapes the video stream.
+    pub fn video_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = vec!["-c:v".into(), self.encoder_name().into()];
+
+        match self.video_codec {
+            VideoCodec::ProRes => {
+                let profile = self.prores_profile.unwrap_or(ProResProfile::Hq) as u8;
+                args.extend([
+                    "-profile:v".into(),
+                    profile.to_string(),
+                    "-vendor".into(),
+                    "apl0".into(),
+                ]);
+            }
+            codec => {
+                match self.rate_control {
+                    RateControl::Crf { value } => {
+                        args.extend(["-crf".into(), value.to_string()]);
+                        // VP9/AV1 only run in constant-quality mode with a zero bitrate target
+                        if matches!(codec, VideoCodec::Vp9 | VideoCodec::Av1) {
+                            args.extend(["-b:v".into(), "0".into()]);
+                        }
+                    }
+                    RateControl::Bitrate { kbps } => {
+                        args.extend(["-b:v".into(), format!("{}k", kbps)])
+                    }
+                }
+                match codec {
+                    VideoCodec::X264 | VideoCodec::X265 => {
+                        let preset = self.preset.as_deref().unwrap_or("medium");
+                        args.extend(["-preset".into(), preset.into()]);
+                    }
+                    VideoCodec::Vp9 | VideoCodec::Av1 => {
+                        args.extend(["-row-mt".into(), "1".into()])
+                    }
+                    VideoCodec::ProRes => {}
+                }
+                if codec == VideoCodec::X265 {
+                    // Lets QuickTime/Safari recognise HEVC in MP4/MOV
+                    args.extend(["-tag:v".into(), "hvc1".into()]);
+                }
+            }
+        }
+
+        let pixel_format = self
+            .pixel_format
+            .as_deref()
+            .unwrap_or(self.default_pixel_format());
+        args.extend(["-pix_fmt".into(), pixel_format.into()]);
+        args
+    }
+
+    /// `-c:a ...` (or `-an`) for the audio stream.
+    pub fn audio_args(&self) -> Vec<String> {
+        let Some(encoder) = self.audio_encoder_name() else {
+            return vec!["-an".into()];
+        };
+        let mut args: Vec<String> = vec!["-c:a".into(), encoder.into()];
+        if matches!(self.audio_codec, AudioCodec::Aac | AudioCodec::Opus) {
+            args.extend([
+                "-b:a".into(),
+                format!("{}k", self.audio_bitrate_kbps.unwrap_or(192)),
+            ]);
+        }
+        args
+    }
+
+    /// Extra arguments for one pass of a two-pass encode.
+    pub fn pass_args(&self, pass: u8, log_prefix: &Path) -> Vec<String> {
+        let log_prefix = log_prefix.to_string_lossy().into_owned();
+        if self.video_codec == VideoCodec::X265 {
+            vec![
+                "-x265-params".into(),
+                format!(
+                    "pass={}:stats={}",
+                    pass,
+                    escape_x265_param(&format!("{}.log", log_prefix))
+                ),
+            ]
+        } else {
+            vec![
+                "-pass".into(),
+                pass.to_string(),
+                "-passlogfile".into(),
+                log_prefix,
+            ]
+        }
+    }
+}
+
+/// Escape a value for the `key=value:key=value` list of `-x265-params`, so
+/// that a Windows drive letter (`C:\...`) does not end the option early.
+fn escape_x265_param(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '\\' | ':' | '=' | '\'') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn x265_two_pass() -> ExportProfile {
+        ExportProfile {
+            video_codec: VideoCodec::X265,
+            rate_control: RateControl::Bitrate { kbps: 8000 },
+            two_pass: true,
+            ..QualityPreset::Medium.profile(Path::new("out.mp4"))
+        }
+    }
+
+    #[test]
+    fn two_pass_needs_a_bitrate() {
+        assert!(x265_two_pass().validate().is_ok());
+        let crf = ExportProfile {
+            rate_control: RateControl::Crf { value: 23 },
+            ..x265_two_pass()
+        };
+        assert!(matches!(crf.validate(), Err(AppError::InvalidArgument(_))));
+        let single_pass = ExportProfile {
+            two_pass: false,
+            ..crf
+        };
+        assert!(single_pass.validate().is_ok());
+    }
+
+    #[test]
+    fn x265_stats_path_keeps_drive_letter() {
+        let args = x265_two_pass().pass_args(1, Path::new(r"C:\Temp\music-visualizer-3"));
+        assert_eq!(
+            args,
+            [
+                "-x265-params",
+                r"pass=1:stats=C\:\\Temp\\music-visualizer-3.log"
+            ]
+        );
+    }
+
+    #[test]
+    fn x265_stats_path_on_unix_is_unchanged() {
+        let args = x265_two_pass().pass_args(2, Path::new("/tmp/music-visualizer-3"));
+        assert_eq!(
+            args,
+            ["-x265-params", "pass=2:stats=/tmp/music-visualizer-3.log"]
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// What FFmpeg is doing for a job.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConversionPhase {
+    /// Decoding the audio to measure its loudness before normalizing it.
+    Measuring,
+    #[default]
+    Encoding,
+}
+
+/// Progress of one conversion, emitted as the `conversion-progress` event.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversionProgress {
+    pub job_id: String,
+    pub phase: ConversionPhase,
+    /// `None` while the input duration is unknown (e.g. MediaRecorder WebM without a duration header).
+    pub percent: Option<f64>,
+    pub fps: Option<f64>,
+    pub eta_seconds: Option<f64>,
+    pub out_time_seconds: f64,
+    pub done: bool,
+}
+
+/// Accumulates the `key=value` blocks FFmpeg writes with `-progress`.
+/// Each block ends with `progress=continue` or `progress=end`.
+#[derive(Default)]
+pub struct ProgressParser {
+    out_time_us: Option<i64>,
+    fps: Option<f64>,
+}
+
+/// One completed `-progress` block.
+pub struct ProgressSnapshot {
+    pub out_time_seconds: f64,
+    pub fps: Option<f64>,
+    pub done: bool,
+}
+
+impl ProgressParser {
+    pub fn push_line(&mut self, line: &str) -> Option<ProgressSnapshot> {
+        let (key, value) = line.trim().split_once('=')?;
+        match key {
+            // `out_time_ms` is in microseconds too (a long-standing FFmpeg quirk)
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(us) = value.parse::<i64>() {
+                    self.out_time_us = Some(us.max(0));
+                }
+            }
+            "fps" => self.fps = value.parse::<f64>().ok().filter(|fps| *fps > 0.0),
+            "progress" => {
+                return Some(ProgressSnapshot {
+                    out_time_seconds: self.out_time_us.unwrap_or(0) as f64 / 1_000_000.0,
+                    fps: self.fps,
+                    done: value == "end",
+                });
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
+impl ProgressSnapshot {
+    /// Percent and ETA against the input duration, extrapolated from wall-clock time so far.
+    pub fn into_progress(
+        self,
+        job_id: &str,
+        duration: Option<f64>,
+        elapsed: Duration,
+    ) -> ConversionProgress {
+        let fraction = duration.filter(|d| *d > 0.0).map(|d| {
+            if self.done {
+                1.0
+            } else {
+                (self.out_time_seconds / d).clamp(0.0, 1.0)
+            }
+        });
+        let eta_seconds = fraction
+            .filter(|f| *f > 0.0)
+            .map(|f| elapsed.as_secs_f64() * (1.0 - f) / f);
+
+        ConversionProgress {
+            job_id: job_id.to_string(),
+            phase: ConversionPhase::Encoding,
+            percent: fraction.map(|f| f * 100.0),
+            fps: self.fps,
+            eta_seconds,
+            out_time_seconds: self.out_time_seconds,
+            done: self.done,
+        }
+    }
+}
+
+/// Parse the `Duration: HH:MM:SS.xx` line FFmpeg prints for its input.
+pub fn parse_duration_line(line: &str) -> Option<f64> {
+    let rest = line.trim_start().strip_prefix("Duration:")?;
+    let timestamp = rest.split(',').next()?.trim();
+    let mut seconds = 0.0;
+    for part in timestamp.split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Captured from `ffmpeg -progress pipe:1 -nostats` (FFmpeg 6.1): the
+    /// first block comes before any packet is muxed, the last one at the end.
+    const PROGRESS: &str = "\
+frame=0
+fps=0.00
+stream_0_0_q=0.0
+bitrate=N/A
+total_size=44
+out_time_us=N/A
+out_time_ms=N/A
+out_time=N/A
+dup_frames=0
+drop_frames=0
+speed=N/A
+progress=continue
+frame=117
+fps=58.41
+stream_0_0_q=28.0
+bitrate= 412.3kbits/s
+total_size=98347
+out_time_us=1908333
+out_time_ms=1908333
+out_time=00:00:01.908333
+dup_frames=0
+drop_frames=0
+speed=0.953x
+progress=continue
+frame=117
+fps=0.00
+stream_0_0_q=28.0
+bitrate= 412.3kbits/s
+total_size=98347
+out_time_us=N/A
+out_time_ms=N/A
+out_time=N/A
+dup_frames=0
+drop_frames=0
+speed=N/A
+progress=continue
+frame=240
+fps=59.87
+stream_0_0_q=-1.0
+bitrate= 398.7kbits/s
+total_size=199350
+out_time_us=4000000
+out_time_ms=4000000
+out_time=00:00:04.000000
+dup_frames=0
+drop_frames=0
+speed=1.99x
+progress=end
+";
+
+    fn snapshots() -> Vec<ProgressSnapshot> {
+        let mut parser = ProgressParser::default();
+        PROGRESS
+            .lines()
+            .filter_map(|line| parser.push_line(line))
+            .collect()
+    }
+
+    #[test]
+    fn one_snapshot_per_block() {
+        let snapshots = snapshots();
+        assert_eq!(snapshots.len(), 4);
+        assert_eq!(
+            snapshots.iter().map(|s| s.done).collect::<Vec<_>>(),
+            [false, false, false, true]
+        );
+    }
+
+    #[test]
+    fn unknown_out_time_keeps_the_last_one() {
+        let snapshots = snapshots();
+        assert_eq!(snapshots[0].out_time_seconds, 0.0);
+        assert!((snapshots[1].out_time_seconds - 1.908333).abs() < 1e-9);
+        assert!((snapshots[2].out_time_seconds - 1.908333).abs() < 1e-9);
+        assert_eq!(snapshots[3].out_time_seconds, 4.0);
+    }
+
+    #[test]
+    fn zero_fps_is_unknown() {
+        let snapshots = snapshots();
+        assert_eq!(snapshots[0].fps, None);
+        assert_eq!(snapshots[1].fps, Some(58.41));
+        assert_eq!(snapshots[2].fps, None);
+    }
+
+    #[test]
+    fn end_block_completes_the_conversion() {
+        let last = snapshots().pop().unwrap();
+        let progress = last.into_progress("job", Some(4.2), Duration::from_secs(2));
+        assert_eq!(progress.percent, Some(100.0));
+        assert_eq!(progress.eta_seconds, Some(0.0));
+        assert!(progress.done);
+    }
+
+    #[test]
+    fn percent_and_eta_follow_out_time() {
+        let snapshot = ProgressSnapshot {
+            out_time_seconds: 2.5,
+            fps: Some(60.0),
+            done: false,
+        };
+        let progress = snapshot.into_progress("job", Some(10.0), Duration::from_secs(3));
+        assert_eq!(progress.percent, Some(25.0));
+        assert_eq!(progress.eta_seconds, Some(9.0));
+    }
+
+    #[test]
+    fn unknown_duration_gives_no_percent() {
+        let snapshot = ProgressSnapshot {
+            out_time_seconds: 2.5,
+            fps: None,
+            done: false,
+        };
+        let progress = snapshot.into_progress("job", None, Duration::from_secs(3));
+        assert_eq!(progress.percent, None);
+        assert_eq!(progress.eta_seconds, None);
+    }
+
+    #[test]
+    fn duration_line() {
+        assert_eq!(
+            parse_duration_line("  Duration: 00:03:25.47, start: 0.000000, bitrate: 192 kb/s"),
+            Some(205.47)
+        );
+        assert_eq!(
+            parse_duration_line("  Duration: 01:00:00.00, start: 0.025057, bitrate: 128 kb/s"),
+            Some(3600.0)
+        );
+        // MediaRecorder WebM has no duration header
+        assert_eq!(
+            parse_duration_line("  Duration: N/A, start: 0.000000, bitrate: N/A"),
+            None
+        );
+        assert_eq!(
+            parse_duration_line("  Stream #0:0: Audio: opus, 48000 Hz, stereo, fltp"),
+            None
+        );
+    }
+}
@@ -0,0 +1,597 @@
+pub mod font;
+
+use std::path::Path;
+
+use image::{ImageError, ImageFormat, Rgb, Rgb32FImage, RgbImage};
+use serde::Deserialize;
+
+use crate::errors::{AppError, AppResult};
+use crate::services::analyzer::spectrogram::{FrequencyScale, Spectrogram};
+use crate::services::colormap::{self, ColormapSpec};
+
+/// Largest width or height accepted for an exported figure.
+const MAX_DIMENSION: u32 = 16384;
+const MIN_DIMENSION: u32 = 64;
+/// Largest EXR figure in pixels. EXR is written as 32-bit float RGB, 12 bytes
+/// a pixel on top of the 8-bit image, so it gets a smaller budget (~400 MB).
+const MAX_EXR_PIXELS: u64 = 8192 * 4096;
+
+fn default_axes() -> bool {
+    true
+}
+
+fn default_background() -> String {
+    "#ffffff".to_string()
+}
+
+/// Size and look of an exported spectrogram figure.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FigureOptions {
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub colormap: ColormapSpec,
+    /// Draw frequency and time axes with tick labels around the plot.
+    #[serde(default = "default_axes")]
+    pub axes: bool,
+    /// `#rrggbb` behind the axes; labels are black or white to contrast with it.
+    #[serde(default = "default_background")]
+    pub background: String,
+}
+
+/// Pixel rectangle the spectrogram is drawn into.
+#[derive(Clone, Copy)]
+struct Plot {
+    left: u32,
+    top: u32,
+    width: u32,
+    height: u32,
+}
+
+/// Maximum over the cells a pixel covers when downsampling (so short events
+/// survive), linear interpolation between cell centres when upsampling.
+fn resample(source: impl Fn(usize) -> f32, len: usize, count: usize) -> Vec<f32> {
+    let ratio = len as f64 / count as f64;
+    (0..count)
+        .map(|i| {
+            let start = i as f64 * ratio;
+            let end = start + ratio;
+            if ratio >= 1.0 {
+                let first = start.floor() as usize;
+                let last = (end.ceil() as usize).clamp(first + 1, len);
+                (first..last).map(&source).fold(0.0, f32::max)
+            } else {
+                let position = ((start + end) / 2.0 - 0.5).clamp(0.0, (len - 1) as f64);
+                let lower = position.floor() as usize;
+                let upper = (lower + 1).min(len - 1);
+                let f = (position - lower as f64) as f32;
+                source(lower) * (1.0 - f) + source(upper) * f
+            }
+        })
+        .collect()
+}
+
+/// Smallest 1, 2 or 5 × 10^n step that is at least `raw`.
+fn nice_step(raw: f64) -> f64 {
+    let magnitude = 10f64.powf(raw.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|step| *step >= raw)
+        .unwrap_or(10.0 * magnitude)
+}
+
+fn time_label(seconds: f64, step: f64) -> String {
+    let decimals = if step >= 1.0 {
+        0
+    } else {
+        (-step.log10()).ceil() as usize
+    };
+    format!("{:.*}", decimals, seconds)
+}
+
+fn frequency_label(hz: f64) -> String {
+    if hz >= 1000.0 {
+        let khz = format!("{:.1}", hz / 1000.0);
+        format!("{}k", khz.trim_end_matches(".0"))
+    } else {
+        format!("{}", hz.round())
+    }
+}
+
+/// Candidate frequency ticks: even steps on a linear axis, 1-2-5 per decade otherwise.
+fn frequency_ticks(scale: FrequencyScale, min: f64, max: f64, max_ticks: usize) -> Vec<f64> {
+    if max <= min {
+        return Vec::new();
+    }
+    match scale {
+        FrequencyScale::Linear => {
+            let step = nice_step((max - min) / max_ticks.max(1) as f64);
+            let mut tick = (min / step).ceil() * step;
+            let mut ticks = Vec::new();
+            while tick <= max + step * 1e-6 {
+                ticks.push(tick);
+                tick += step;
+            }
+            ticks
+        }
+        FrequencyScale::Log | FrequencyScale::Mel => {
+            let mut ticks = Vec::new();
+            let mut decade = 10f64.powf(min.max(1.0).log10().floor());
+            while decade <= max {
+                for m in [1.0, 2.0, 5.0] {
+                    let tick = m * decade;
+                    if tick >= min && tick <= max {
+                        ticks.push(tick);
+                    }
+                }
+                decade *= 10.0;
+            }
+            ticks
+        }
+    }
+}
+
+/// Fractional row for `hz` from the row centre frequencies (ascending).
+fn frequency_row(frequencies: &[f32], hz: f64) -> Option<f64> {
+    let hz = hz as f32;
+    let upper = frequencies.iter().position(|f| *f >= hz)?;
+    if upper == 0 {
+        return (frequencies[0] == hz).then_some(0.0);
+    }
+    let (f0, f1) = (frequencies[upper - 1], frequencies[upper]);
+    let f = if f1 > f0 { (hz - f0) / (f1 - f0) } else { 0.0 };
+    Some((upper - 1) as f64 + f as f64)
+}
+
+struct Canvas {
+    image: RgbImage,
+    ink: Rgb<u8>,
+    scale: u32,
+}
+
+impl Canvas {
+    fn put(&mut self, x: i64, y: i64) {
+        if x >= 0 && y >= 0 && (x as u32) < self.image.width() && (y as u32) < self.image.height() {
+            self.image.put_pixel(x as u32, y as u32, self.ink);
+        }
+    }
+
+    fn fill(&mut self, x: i64, y: i64, width: i64, height: i64) {
+        for dy in 0..height {
+            for dx in 0..width {
+                self.put(x + dx, y + dy);
+            }
+        }
+    }
+
+    fn text(&mut self, text: &str, x: i64, y: i64, vertical: bool) {
+        let scale = self.scale;
+        let mut points = Vec::new();
+        font::rasterize(text, scale, vertical, |dx, dy| {
+            points.push((x + dx, y + dy))
+        });
+        for (px, py) in points {
+            self.put(px, py);
+        }
+    }
+
+    fn text_height(&self) -> u32 {
+        font::GLYPH_HEIGHT * self.scale
+    }
+}
+
+fn draw_plot(canvas: &mut Canvas, spectrogram: &Spectrogram, lut: &colormap::Lut, plot: Plot) {
+    let frames = spectrogram.frame_count;
+    let bins = spectrogram.bin_count;
+    let width = plot.width as usize;
+    let height = plot.height as usize;
+    let values = &spectrogram.values;
+
+    // Time first (per row), then frequency (per column)
+    let mut columns = vec![0.0f32; bins * width];
+    for bin in 0..bins {
+        let row = resample(|frame| values[frame * bins + bin] as f32, frames, width);
+        columns[bin * width..(bin + 1) * width].copy_from_slice(&row);
+    }
+    for x in 0..width {
+        let column = resample(|bin| columns[bin * width + x], bins, height);
+        for (row, value) in column.iter().enumerate() {
+            let color = lut.color(value.round().clamp(0.0, 255.0) as u8);
+            let y = plot.top as usize + height - 1 - row;
+            canvas
+                .image
+                .put_pixel(plot.left + x as u32, y as u32, Rgb(color));
+        }
+    }
+}
+
+fn draw_axes(
+    canvas: &mut Canvas,
+    spectrogram: &Spectrogram,
+    scale: FrequencyScale,
+    plot: Plot,
+    ticks: &[(f64, String)],
+) {
+    let line = (canvas.scale / 2).max(1) as i64;
+    let tick = 3 * canvas.scale as i64;
+    let pad = 2 * canvas.scale as i64;
+    let text_height = canvas.text_height() as i64;
+    let (left, top) = (plot.left as i64, plot.top as i64);
+    let (right, bottom) = (left + plot.width as i64, top + plot.height as i64);
+
+    // Frame
+    canvas.fill(left - line, top - line, plot.width as i64 + 2 * line, line);
+    canvas.fill(left - line, bottom, plot.width as i64 + 2 * line, line);
+    canvas.fill(left - line, top, line, plot.height as i64);
+    canvas.fill(right, top, line, plot.height as i64);
+
+    // Time axis: x of a frame is the centre of its column
+    let frames = spectrogram.frame_count as f64;
+    let duration = frames * spectrogram.frame_duration;
+    let label_width = font::text_width(&time_label(duration, 0.1), canvas.scale) as f64;
+    let max_ticks = ((plot.width as f64 / (label_width * 2.0)).floor() as usize).max(1);
+    let step = nice_step(duration / max_ticks as f64);
+    for index in 0..=(duration / step).floor() as usize {
+        let seconds = index as f64 * step;
+        let x = left
+            + ((seconds / spectrogram.frame_duration + 0.5) * plot.width as f64 / frames) as i64;
+        if x <= right {
+            let label = time_label(seconds, step);
+            canvas.fill(x, bottom + line, line, tick);
+            let width = font::text_width(&label, canvas.scale) as i64;
+            canvas.text(&label, x - width / 2, bottom + line + tick + pad, false);
+        }
+    }
+    let title = "Time (s)";
+    let width = font::text_width(title, canvas.scale) as i64;
+    canvas.text(
+        title,
+        left + (plot.width as i64 - width) / 2,
+        bottom + line + tick + 3 * pad + text_height,
+        false,
+    );
+
+    // Frequency axis, skipping labels that would overlap the previous one
+    let bins = spectrogram.bin_count as f64;
+    let mut last_y = i64::MAX;
+    for (hz, label) in ticks {
+        let Some(row) = frequency_row(&spectrogram.frequencies, *hz) else {
+            continue;
+        };
+        let y = bottom - ((row + 0.5) * plot.height as f64 / bins) as i64;
+        if last_y - y < text_height * 2 {
+            continue;
+        }
+        last_y = y;
+        canvas.fill(left - line - tick, y, tick, line);
+        let width = font::text_width(label, canvas.scale) as i64;
+        canvas.text(
+            label,
+            left - line - tick - pad - width,
+            y - text_height / 2,
+            false,
+        );
+    }
+    let title = match scale {
+        FrequencyScale::Linear => "Frequency (Hz)",
+        FrequencyScale::Log => "Frequency (Hz, log)",
+        FrequencyScale::Mel => "Frequency (Hz, mel)",
+    };
+    let width = font::text_width(title, canvas.scale) as i64;
+    canvas.text(title, pad, top + (plot.height as i64 + width) / 2, true);
+}
+
+/// Render a spectrogram (with axes unless disabled) into an RGB image.
+pub fn render(
+    spectrogram: &Spectrogram,
+    scale: FrequencyScale,
+    options: &FigureOptions,
+) -> AppResult<RgbImage> {
+    for (name, value) in [("width", options.width), ("height", options.height)] {
+        if !(MIN_DIMENSION..=MAX_DIMENSION).contains(&value) {
+            return Err(AppError::InvalidArgument(format!(
+                "Image {} must be between {} and {} pixels, got {}",
+                name, MIN_DIMENSION, MAX_DIMENSION, value
+            )));
+        }
+    }
+    if spectrogram.frame_count == 0 || spectrogram.bin_count == 0 {
+        return Err(AppError::InvalidArgument(
+            "The audio is too short for a spectrogram".to_string(),
+        ));
+    }
+
+    let lut = colormap::lut(&options.colormap)?;
+    let background = colormap::parse_hex(&options.background)?;
+    let luminance = 0.2126 * background[0] as f32
+        + 0.7152 * background[1] as f32
+        + 0.0722 * background[2] as f32;
+    let ink = if luminance > 127.5 {
+        Rgb([0, 0, 0])
+    } else {
+        Rgb([255, 255, 255])
+    };
+    let mut canvas = Canvas {
+        image: RgbImage::from_pixel(options.width, options.height, Rgb(background)),
+        ink,
+        scale: (options.width.min(options.height) / 360).clamp(1, 8),
+    };
+
+    if !options.axes {
+        let plot = Plot {
+            left: 0,
+            top: 0,
+            width: options.width,
+            height: options.height,
+        };
+        draw_plot(&mut canvas, spectrogram, &lut, plot);
+        return Ok(canvas.image);
+    }
+
+    let min_hz = *spectrogram.frequencies.first().unwrap_or(&0.0) as f64;
+    let max_hz = *spectrogram.frequencies.last().unwrap_or(&0.0) as f64;
+    let text_height = canvas.text_height();
+    let max_ticks = ((options.height / (text_height * 3)) as usize).max(2);
+    let ticks: Vec<(f64, String)> = frequency_ticks(scale, min_hz, max_hz, max_ticks)
+        .into_iter()
+        .map(|hz| (hz, frequency_label(hz)))
+        .collect();
+    let label_width = ticks
+        .iter()
+        .map(|(_, label)| font::text_width(label, canvas.scale))
+        .max()
+        .unwrap_or(0);
+
+    // Margins: title, labels, ticks and frame on the left and bottom
+    let pad = 2 * canvas.scale;
+    let tick = 3 * canvas.scale;
+    let left = pad + text_height + 2 * pad + label_width + pad + tick + canvas.scale;
+    let bottom = canvas.scale + tick + 3 * pad + 2 * text_height + pad;
+    let top = text_height;
+    let right = font::text_width("0000", canvas.scale) / 2 + pad;
+    if options.width < left + right + MIN_DIMENSION / 2
+        || options.height < top + bottom + MIN_DIMENSION / 2
+    {
+        return Err(AppError::InvalidArgument(
+            "The image is too small for axis labels".to_string(),
+        ));
+    }
+    let plot = Plot {
+        left,
+        top,
+        width: options.width - left - right,
+        height: options.height - top - bottom,
+    };
+    draw_plot(&mut canvas, spectrogram, &lut, plot);
+    draw_axes(&mut canvas, spectrogram, scale, plot, &ticks);
+    Ok(canvas.image)
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let c = value as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// The format `path` is saved in, checked before a `width` x `height`
+/// figure is rendered for it.
+pub fn image_format(path: &Path, width: u32, height: u32) -> AppResult<ImageFormat> {
+    let format = ImageFormat::from_path(path)
+        .ok()
+        .filter(|format| {
+            matches!(
+                format,
+                ImageFormat::Png | ImageFormat::Tiff | ImageFormat::OpenExr
+            )
+        })
+        .ok_or_else(|| {
+            AppError::UnsupportedFormat(format!(
+                "{}: images can be saved as .png, .tiff or .exr",
+                path.display()
+            ))
+        })?;
+    if format == ImageFormat::OpenExr && u64::from(width) * u64::from(height) > MAX_EXR_PIXELS {
+        return Err(AppError::InvalidArgument(format!(
+            "EXR figures are limited to {} megapixels, got {}x{}; use PNG or TIFF for larger images",
+            MAX_EXR_PIXELS >> 20,
+            width,
+            height
+        )));
+    }
+    Ok(format)
+}
+
+/// Write a figure as PNG, TIFF or EXR (linear float), chosen by extension.
+pub fn save(image: &RgbImage, path: &Path) -> AppResult<()> {
+    let format = image_format(path, image.width(), image.height())?;
+    let result = if format == ImageFormat::OpenExr {
+        let linear = Rgb32FImage::from_fn(image.width(), image.height(), |x, y| {
+            Rgb(image.get_pixel(x, y).0.map(srgb_to_linear))
+        });
+        linear.save_with_format(path, format)
+    } else {
+        image.save_with_format(path, format)
+    };
+    result.map_err(|error| match error {
+        ImageError::IoError(error) => AppError::io(path, "write", error),
+        other => AppError::Io {
+            path: Some(path.to_string_lossy().into_owned()),
+            message: format!("Failed to write {}: {}", path.display(), other),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::colormap::Colormap;
+
+    /// `frames` x `bins` cells of `value`, rows 100 Hz apart from 100 Hz.
+    fn spectrogram(frames: usize, bins: usize, value: u8) -> Spectrogram {
+        Spectrogram {
+            sample_rate: 44_100,
+            window_size: 1024,
+            hop_size: 441,
+            frame_count: frames,
+            bin_count: bins,
+            frame_duration: 0.01,
+            frequencies: (1..=bins).map(|bin| bin as f32 * 100.0).collect(),
+            max_decibels: 0.0,
+            values: vec![value; frames * bins],
+        }
+    }
+
+    fn options(width: u32, height: u32, axes: bool) -> FigureOptions {
+        FigureOptions {
+            width,
+            height,
+            colormap: ColormapSpec::Named(Colormap::Viridis),
+            axes,
+            background: "#ffffff".to_string(),
+        }
+    }
+
+    #[test]
+    fn dimensions_outside_the_bounds_are_refused() {
+        let spectrogram = spectrogram(10, 10, 0);
+        for (width, height) in [
+            (MIN_DIMENSION - 1, 480),
+            (640, MIN_DIMENSION - 1),
+            (MAX_DIMENSION + 1, 480),
+            (640, MAX_DIMENSION + 1),
+        ] {
+            assert!(matches!(
+                render(
+                    &spectrogram,
+                    FrequencyScale::Linear,
+                    &options(width, height, false)
+                ),
+                Err(AppError::InvalidArgument(_))
+            ));
+        }
+        let image = render(
+            &spectrogram,
+            FrequencyScale::Linear,
+            &options(MIN_DIMENSION, MIN_DIMENSION, false),
+        )
+        .unwrap();
+        assert_eq!(image.dimensions(), (MIN_DIMENSION, MIN_DIMENSION));
+    }
+
+    #[test]
+    fn exr_has_a_smaller_pixel_budget() {
+        let exr = Path::new("figure.exr");
+        assert_eq!(image_format(exr, 8192, 4096).unwrap(), ImageFormat::OpenExr);
+        assert!(matches!(
+            image_format(exr, 8192, 4097),
+            Err(AppError::InvalidArgument(_))
+        ));
+        assert_eq!(
+            image_format(Path::new("figure.png"), MAX_DIMENSION, MAX_DIMENSION).unwrap(),
+            ImageFormat::Png
+        );
+        assert_eq!(
+            image_format(Path::new("figure.tif"), 640, 480).unwrap(),
+            ImageFormat::Tiff
+        );
+    }
+
+    #[test]
+    fn other_extensions_are_unsupported() {
+        for path in ["figure.jpg", "figure.webp", "figure"] {
+            assert!(matches!(
+                image_format(Path::new(path), 640, 480),
+                Err(AppError::UnsupportedFormat(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn ticks_step_by_one_two_or_five() {
+        let cases = [
+            (0.012, 0.02),
+            (0.3, 0.5),
+            (1.0, 1.0),
+            (1.1, 2.0),
+            (3.0, 5.0),
+            (7.0, 10.0),
+            (45.0, 50.0),
+        ];
+        for (raw, step) in cases {
+            assert!((nice_step(raw) - step).abs() < 1e-9, "{} -> {}", raw, step);
+        }
+        assert_eq!(
+            frequency_ticks(FrequencyScale::Linear, 0.0, 1000.0, 5),
+            [0.0, 200.0, 400.0, 600.0, 800.0, 1000.0]
+        );
+        assert_eq!(
+            frequency_ticks(FrequencyScale::Log, 20.0, 1000.0, 5),
+            [20.0, 50.0, 100.0, 200.0, 500.0, 1000.0]
+        );
+        assert_eq!(frequency_label(1500.0), "1.5k");
+        assert_eq!(frequency_label(2000.0), "2k");
+        assert_eq!(time_label(1.5, 0.5), "1.5");
+    }
+
+    #[test]
+    fn frequencies_map_to_fractional_rows() {
+        let frequencies = [100.0, 200.0, 400.0];
+        assert_eq!(frequency_row(&frequencies, 100.0), Some(0.0));
+        assert_eq!(frequency_row(&frequencies, 150.0), Some(0.5));
+        assert_eq!(frequency_row(&frequencies, 300.0), Some(1.5));
+        assert_eq!(frequency_row(&frequencies, 400.0), Some(2.0));
+        assert_eq!(frequency_row(&frequencies, 50.0), None);
+        assert_eq!(frequency_row(&frequencies, 500.0), None);
+    }
+
+    #[test]
+    fn resampling_keeps_peaks_and_interpolates() {
+        let cells = [0.0, 5.0, 1.0, 0.0];
+        assert_eq!(resample(|i| cells[i], 4, 2), [5.0, 1.0]);
+        assert_eq!(resample(|i| cells[i], 4, 4), cells);
+        let cells = [0.0, 4.0];
+        assert_eq!(resample(|i| cells[i], 2, 4), [0.0, 1.0, 3.0, 4.0]);
+    }
+
+    #[test]
+    fn png_figure_has_the_requested_size_and_axes() {
+        let spectrogram = spectrogram(200, 64, 0);
+        let quiet = [68, 1, 84];
+
+        let bare = render(
+            &spectrogram,
+            FrequencyScale::Linear,
+            &options(320, 240, false),
+        )
+        .unwrap();
+        assert!(bare.pixels().all(|pixel| pixel.0 == quiet));
+
+        let image = render(
+            &spectrogram,
+            FrequencyScale::Linear,
+            &options(320, 240, true),
+        )
+        .unwrap();
+        let path = std::env::temp_dir().join(format!(
+            "music-visualizer-figure-{}.png",
+            std::process::id()
+        ));
+        save(&image, &path).unwrap();
+        let saved = image::open(&path);
+        let _ = std::fs::remove_file(&path);
+        let saved = saved.unwrap().to_rgb8();
+        assert_eq!(saved.dimensions(), (320, 240));
+
+        // Background in the corner, the plot in the middle, black ink for the
+        // frame, ticks and labels
+        assert_eq!(saved.get_pixel(0, 0).0, [255, 255, 255]);
+        assert_eq!(saved.get_pixel(200, 100).0, quiet);
+        let ink = saved.pixels().filter(|pixel| pixel.0 == [0, 0, 0]).count();
+        assert!(ink > 500, "{} black pixels", ink);
+    }
+}
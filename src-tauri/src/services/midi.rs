@@ -0,0 +1,640 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+
+use midly::{Format, MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+use serde::Serialize;
+
+use crate::domain::timeline::{TickBase, Timeline};
+use crate::errors::{AppError, AppResult};
+
+const MAJOR_KEYS: [&str; 15] = [
+    "Cb", "Gb", "Db", "Ab", "Eb", "Bb", "F", "C", "G", "D", "A", "E", "B", "F#", "C#",
+];
+const MINOR_KEYS: [&str; 15] = [
+    "Ab", "Eb", "Bb", "F", "C", "G", "D", "A", "E", "B", "F#", "C#", "G#", "D#", "A#",
+];
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MidiNote {
+    pub pitch: u8,
+    pub velocity: u8,
+    pub channel: u8,
+    pub start_tick: u64,
+    pub end_tick: u64,
+    /// Seconds from the start of the file, following every tempo change.
+    pub start: f64,
+    pub end: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProgramChange {
+    pub tick: u64,
+    pub seconds: f64,
+    pub channel: u8,
+    pub program: u8,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ControlChange {
+    pub tick: u64,
+    pub seconds: f64,
+    pub channel: u8,
+    pub controller: u8,
+    pub value: u8,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MidiTrack {
+    pub index: usize,
+    pub name: Option<String>,
+    /// Notes sorted by start tick.
+    pub notes: Vec<MidiNote>,
+    pub program_changes: Vec<ProgramChange>,
+    pub control_changes: Vec<ControlChange>,
+    /// Tick of the track's last event.
+    pub end_tick: u64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TempoChange {
+    pub tick: u64,
+    pub seconds: f64,
+    pub microseconds_per_quarter: u32,
+    pub bpm: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeSignature {
+    pub tick: u64,
+    pub seconds: f64,
+    pub numerator: u8,
+    pub denominator: u32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeySignature {
+    pub tick: u64,
+    pub seconds: f64,
+    /// Positive for sharps, negative for flats.
+    pub sharps: i8,
+    pub minor: bool,
+    /// e.g. `"Eb major"`, `"F# minor"`.
+    pub name: String,
+}
+
+/// One bar of the file's meter map. A meter change that does not fall on a
+/// bar line shortens the bar it interrupts.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MidiBar {
+    /// One-based.
+    pub number: u32,
+    pub start_tick: u64,
+    pub end_tick: u64,
+    pub start: f64,
+    pub end: f64,
+    pub numerator: u8,
+    pub denominator: u32,
+    /// Ticks per beat (one `1/denominator` note) in this bar.
+    pub beat_ticks: f64,
+}
+
+/// A parsed Standard MIDI File (type 0 or 1).
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MidiFile {
+    pub format: u8,
+    /// Ticks per quarter note, or `None` for SMPTE timecode files.
+    pub ticks_per_quarter: Option<u16>,
+    pub duration_ticks: u64,
+    /// Seconds until the last event of any track.
+    pub duration: f64,
+    pub tracks: Vec<MidiTrack>,
+    /// Every tempo in effect, starting at tick 0 (120 BPM if the file sets none there).
+    pub tempos: Vec<TempoChange>,
+    /// Meter changes, starting at tick 0 (4/4 if the file sets none there).
+    pub time_signatures: Vec<TimeSignature>,
+    pub key_signatures: Vec<KeySignature>,
+    /// Every bar up to `duration_ticks`, with start/end in seconds.
+    pub bars: Vec<MidiBar>,
+}
+
+/// A note of any track, from a time-window query.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowNote {
+    /// Index into `MidiFile::tracks`.
+    pub track: usize,
+    #[serde(flatten)]
+    pub note: MidiNote,
+}
+
+/// Notes and bars that overlap `start..end` seconds.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MidiWindow {
+    pub start: f64,
+    pub end: f64,
+    /// Sorted by start time.
+    pub notes: Vec<WindowNote>,
+    pub bars: Vec<MidiBar>,
+}
+
+/// A parsed file with the notes of every track merged by start time, so that
+/// the notes sounding in a time window can be found without a full scan.
+pub struct IndexedMidi {
+    pub file: MidiFile,
+    notes: Vec<WindowNote>,
+    /// Longest note in seconds; bounds how far back a window has to look.
+    longest: f64,
+}
+
+impl IndexedMidi {
+    pub fn new(file: MidiFile) -> Self {
+        let mut notes: Vec<WindowNote> = file
+            .tracks
+            .iter()
+            .flat_map(|track| {
+                track.notes.iter().map(|note| WindowNote {
+                    track: track.index,
+                    note: note.clone(),
+                })
+            })
+            .collect();
+        notes.sort_by(|a, b| {
+            a.note
+                .start
+                .total_cmp(&b.note.start)
+                .then(a.note.pitch.cmp(&b.note.pitch))
+        });
+        let longest = notes
+            .iter()
+            .map(|n| n.note.end - n.note.start)
+            .fold(0.0, f64::max);
+        IndexedMidi {
+            file,
+            notes,
+            longest,
+        }
+    }
+
+    /// Lowest and highest pitch of any note, if the file has notes.
+    pub fn pitch_range(&self) -> Option<(u8, u8)> {
+        let lowest = self.notes.iter().map(|n| n.note.pitch).min()?;
+        let highest = self.notes.iter().map(|n| n.note.pitch).max()?;
+        Some((lowest, highest))
+    }
+
+    pub fn note_count(&self) -> usize {
+        self.notes.len()
+    }
+
+    /// Notes sounding at any point in `start..end`, and the bars overlapping it.
+    pub fn window(&self, start: f64, end: f64) -> AppResult<MidiWindow> {
+        if !start.is_finite() || !end.is_finite() || end < start {
+            return Err(AppError::InvalidArgument(format!(
+                "Invalid time window: {}..{}",
+                start, end
+            )));
+        }
+        let first = self
+            .notes
+            .partition_point(|n| n.note.start < start - self.longest);
+        let last = self.notes.partition_point(|n| n.note.start < end);
+        let notes = self.notes[first..last.max(first)]
+            .iter()
+            // Zero-length notes still show at their start
+            .filter(|n| n.note.end > start || n.note.start >= start)
+            .cloned()
+            .collect();
+
+        let bars = &self.file.bars;
+        let first_bar = bars.partition_point(|bar| bar.end <= start);
+        let last_bar = bars.partition_point(|bar| bar.start < end);
+        Ok(MidiWindow {
+            start,
+            end,
+            notes,
+            bars: bars[first_bar..last_bar.max(first_bar)].to_vec(),
+        })
+    }
+}
+
+fn key_name(sharps: i8, minor: bool) -> String {
+    let index = (sharps.clamp(-7, 7) + 7) as usize;
+    if minor {
+        format!("{} minor", MINOR_KEYS[index])
+    } else {
+        format!("{} major", MAJOR_KEYS[index])
+    }
+}
+
+/// Events of one track before ticks are converted to seconds.
+#[derive(Default)]
+struct RawTrack {
+    name: Option<String>,
+    /// `(pitch, velocity, channel, start, end)` in ticks.
+    notes: Vec<(u8, u8, u8, u64, u64)>,
+    programs: Vec<(u64, u8, u8)>,
+    controls: Vec<(u64, u8, u8, u8)>,
+    end_tick: u64,
+}
+
+#[derive(Default)]
+struct RawMeta {
+    tempos: Vec<(u64, u32)>,
+    /// `(tick, numerator, denominator)`
+    meters: Vec<(u64, u8, u32)>,
+    keys: Vec<(u64, i8, bool)>,
+}
+
+fn read_track(events: &[midly::TrackEvent], meta: &mut RawMeta) -> RawTrack {
+    let mut track = RawTrack::default();
+    // Sounding notes per (channel, pitch); overlapping repeats end first-in first-out
+    let mut open: HashMap<(u8, u8), VecDeque<(u64, u8)>> = HashMap::new();
+    let mut tick = 0u64;
+
+    for event in events {
+        tick += event.delta.as_int() as u64;
+        match event.kind {
+            TrackEventKind::Midi { channel, message } => {
+                let channel = channel.as_int();
+                match message {
+                    MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => {
+                        open.entry((channel, key.as_int()))
+                            .or_default()
+                            .push_back((tick, vel.as_int()));
+                    }
+                    MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => {
+                        let pitch = key.as_int();
+                        if let Some((start, velocity)) = open
+                            .get_mut(&(channel, pitch))
+                            .and_then(VecDeque::pop_front)
+                        {
+                            track.notes.push((pitch, velocity, channel, start, tick));
+                        }
+                    }
+                    MidiMessage::ProgramChange { program } => {
+                        track.programs.push((tick, channel, program.as_int()))
+                    }
+                    MidiMessage::Controller { controller, value } => {
+                        track
+                            .controls
+                            .push((tick, channel, controller.as_int(), value.as_int()))
+                    }
+                    _ => {}
+                }
+            }
+            TrackEventKind::Meta(message) => match message {
+                MetaMessage::TrackName(name) if track.name.is_none() => {
+                    let name = String::from_utf8_lossy(name).trim().to_string();
+                    if !name.is_empty() {
+                        track.name = Some(name);
+                    }
+                }
+                MetaMessage::Tempo(tempo) => meta.tempos.push((tick, tempo.as_int())),
+                MetaMessage::TimeSignature(numerator, denominator, _, _)
+                    if numerator > 0 && denominator < 32 =>
+                {
+                    meta.meters.push((tick, numerator, 1 << denominator));
+                }
+                MetaMessage::KeySignature(sharps, minor) => meta.keys.push((tick, sharps, minor)),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    // Notes still sounding at the end of the track stop there
+    for ((channel, pitch), starts) in open {
+        for (start, velocity) in starts {
+            track.notes.push((pitch, velocity, channel, start, tick));
+        }
+    }
+    track
+        .notes
+        .sort_by_key(|&(pitch, _, _, start, _)| (start, pitch));
+    track.end_tick = tick;
+    track
+}
+
+/// Parse SMF bytes. Type 2 (independent sequences) files are rejected because
+/// their tracks do not share a timeline.
+pub fn parse(bytes: &[u8]) -> AppResult<MidiFile> {
+    let smf = Smf::parse(bytes)
+        .map_err(|e| AppError::UnsupportedFormat(format!("invalid MIDI data ({})", e)))?;
+    let format = match smf.header.format {
+        Format::SingleTrack => 0,
+        Format::Parallel => 1,
+        Format::Sequential => {
+            return Err(AppError::UnsupportedFormat(
+                "MIDI type 2 (sequential) files are not supported".to_string(),
+            ))
+        }
+    };
+    let (base, ticks_per_quarter) = match smf.header.timing {
+        Timing::Metrical(ppq) if ppq.as_int() > 0 => {
+            (TickBase::Metrical(ppq.as_int()), Some(ppq.as_int()))
+        }
+        Timing::Timecode(fps, subframes) if subframes > 0 => (
+            TickBase::Timecode(fps.as_f32() as f64 * subframes as f64),
+            None,
+        ),
+        _ => {
+            return Err(AppError::Decode(
+                "MIDI header division has 0 ticks per beat or frame".to_string(),
+            ))
+        }
+    };
+
+    let mut meta = RawMeta::default();
+    let raw_tracks: Vec<RawTrack> = smf
+        .tracks
+        .iter()
+        .map(|events| read_track(events, &mut meta))
+        .collect();
+    let timeline = Timeline::new(base, &meta.tempos, &meta.meters)?;
+    let seconds = |tick: u64| timeline.seconds_at(tick);
+
+    let tracks: Vec<MidiTrack> = raw_tracks
+        .into_iter()
+        .enumerate()
+        .map(|(index, raw)| MidiTrack {
+            index,
+            name: raw.name,
+            notes: raw
+                .notes
+                .into_iter()
+                .map(
+                    |(pitch, velocity, channel, start_tick, end_tick)| MidiNote {
+                        pitch,
+                        velocity,
+                        channel,
+                        start_tick,
+                        end_tick,
+                        start: seconds(start_tick),
+                        end: seconds(end_tick),
+                    },
+                )
+                .collect(),
+            program_changes: raw
+                .programs
+                .into_iter()
+                .map(|(tick, channel, program)| ProgramChange {
+                    tick,
+                    seconds: seconds(tick),
+                    channel,
+                    program,
+                })
+                .collect(),
+            control_changes: raw
+                .controls
+                .into_iter()
+                .map(|(tick, channel, controller, value)| ControlChange {
+                    tick,
+                    seconds: seconds(tick),
+                    channel,
+                    controller,
+                    value,
+                })
+                .collect(),
+            end_tick: raw.end_tick,
+        })
+        .collect();
+
+    let tempos = timeline
+        .tempo
+        .segments()
+        .iter()
+        .map(|segment| TempoChange {
+            tick: segment.tick,
+            seconds: segment.seconds,
+            microseconds_per_quarter: segment.microseconds_per_quarter,
+            bpm: 60_000_000.0 / segment.microseconds_per_quarter as f64,
+        })
+        .collect();
+
+    let time_signatures = timeline
+        .meter
+        .segments()
+        .iter()
+        .map(|segment| TimeSignature {
+            tick: segment.tick,
+            seconds: seconds(segment.tick),
+            numerator: segment.numerator,
+            denominator: segment.denominator,
+        })
+        .collect();
+
+    meta.keys.sort_by_key(|(tick, _, _)| *tick);
+    let key_signatures = meta
+        .keys
+        .into_iter()
+        .map(|(tick, sharps, minor)| KeySignature {
+            tick,
+            seconds: seconds(tick),
+            sharps,
+            minor,
+            name: key_name(sharps, minor),
+        })
+        .collect();
+
+    let duration_ticks = tracks.iter().map(|track| track.end_tick).max().unwrap_or(0);
+    let bars = timeline
+        .meter
+        .bars(duration_ticks)
+        .into_iter()
+        .map(|bar| MidiBar {
+            number: bar.number,
+            start_tick: bar.start_tick,
+            end_tick: bar.end_tick,
+            start: seconds(bar.start_tick),
+            end: seconds(bar.end_tick),
+            numerator: bar.numerator,
+            denominator: bar.denominator,
+            beat_ticks: base.ticks_per_quarter() * 4.0 / bar.denominator as f64,
+        })
+        .collect();
+    Ok(MidiFile {
+        format,
+        ticks_per_quarter,
+        duration_ticks,
+        duration: seconds(duration_ticks),
+        tracks,
+        tempos,
+        time_signatures,
+        key_signatures,
+        bars,
+    })
+}
+
+/// Read and parse a `.mid` / `.midi` file.
+pub fn parse_file(path: &Path) -> AppResult<MidiFile> {
+    let bytes = std::fs::read(path).map_err(|e| AppError::io(path, "read", e))?;
+    parse(&bytes).map_err(|error| match error {
+        AppError::UnsupportedFormat(message) => {
+            AppError::UnsupportedFormat(format!("{}: {}", path.display(), message))
+        }
+        AppError::Decode(message) => AppError::Decode(format!("{}: {}", path.display(), message)),
+        other => other,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const END: &[u8] = &[0xff, 0x2f, 0];
+
+    /// An `MTrk` chunk from `(delta, event)` pairs; the end-of-track event is added.
+    fn track(events: &[(u32, &[u8])]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (delta, event) in events.iter().chain([&(0, END)]) {
+            let mut groups = vec![(delta & 0x7f) as u8];
+            let mut rest = delta >> 7;
+            while rest > 0 {
+                groups.push((rest & 0x7f) as u8 | 0x80);
+                rest >>= 7;
+            }
+            data.extend(groups.iter().rev());
+            data.extend_from_slice(event);
+        }
+        let mut chunk = b"MTrk".to_vec();
+        chunk.extend((data.len() as u32).to_be_bytes());
+        chunk.extend(data);
+        chunk
+    }
+
+    fn smf(format: u8, division: [u8; 2], tracks: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = b"MThd\0\0\0\x06\0".to_vec();
+        bytes.push(format);
+        bytes.extend((tracks.len() as u16).to_be_bytes());
+        bytes.extend(division);
+        for track in tracks {
+            bytes.extend(track);
+        }
+        bytes
+    }
+
+    fn tempo(microseconds_per_quarter: u32) -> [u8; 6] {
+        let [_, a, b, c] = microseconds_per_quarter.to_be_bytes();
+        [0xff, 0x51, 3, a, b, c]
+    }
+
+    fn seconds(note: &MidiNote) -> (f64, f64) {
+        (note.start, note.end)
+    }
+
+    #[test]
+    fn smpte_division_sets_ticks_per_second() {
+        // 25 fps (-25 as the high byte), 40 ticks per frame: 1000 ticks a second
+        let notes = track(&[(500, &[0x90, 60, 100]), (1000, &[0x80, 60, 0])]);
+        let file = parse(&smf(0, [0xe7, 40], &[notes])).unwrap();
+        assert_eq!(file.ticks_per_quarter, None);
+        assert_eq!(seconds(&file.tracks[0].notes[0]), (0.5, 1.5));
+        assert_eq!(file.duration, 1.5);
+    }
+
+    #[test]
+    fn zero_ticks_per_unit_is_refused() {
+        for division in [[0xe7, 0], [0, 0]] {
+            assert!(matches!(
+                parse(&smf(0, division, &[track(&[])])),
+                Err(AppError::Decode(_))
+            ));
+        }
+    }
+
+    /// Type 1 at 480 ticks a quarter: a tempo track that goes from 120 to
+    /// 240 bpm at tick 960 (1 s), then a track of notes around the change.
+    fn tempo_change(notes: &[(u32, &[u8])]) -> MidiFile {
+        let tempos = track(&[(0, &tempo(500_000)), (960, &tempo(250_000))]);
+        parse(&smf(1, [0x01, 0xe0], &[tempos, track(notes)])).unwrap()
+    }
+
+    #[test]
+    fn notes_on_both_sides_of_a_tempo_change() {
+        let file = tempo_change(&[
+            (0, &[0x90, 60, 100]),
+            (480, &[0x80, 60, 0]),
+            (0, &[0x90, 62, 100]),
+            (960, &[0x80, 62, 0]),
+            (0, &[0x90, 64, 100]),
+            (480, &[0x80, 64, 0]),
+        ]);
+        let notes = &file.tracks[1].notes;
+        // Before, across and after the change: 1 s per 960 ticks, then 0.5 s
+        assert_eq!(seconds(&notes[0]), (0.0, 0.5));
+        assert_eq!(seconds(&notes[1]), (0.5, 1.25));
+        assert_eq!(seconds(&notes[2]), (1.25, 1.5));
+        assert_eq!(file.duration, 1.5);
+
+        let bpm: Vec<(u64, f64, f64)> = file
+            .tempos
+            .iter()
+            .map(|tempo| (tempo.tick, tempo.seconds, tempo.bpm))
+            .collect();
+        assert_eq!(bpm, [(0, 0.0, 120.0), (960, 1.0, 240.0)]);
+    }
+
+    #[test]
+    fn note_on_with_velocity_zero_ends_the_note() {
+        let file = tempo_change(&[(0, &[0x91, 60, 90]), (240, &[0x91, 60, 0])]);
+        let note = &file.tracks[1].notes[0];
+        assert_eq!(file.tracks[1].notes.len(), 1);
+        assert_eq!((note.channel, note.velocity), (1, 90));
+        assert_eq!((note.start_tick, note.end_tick), (0, 240));
+    }
+
+    #[test]
+    fn overlapping_notes_of_one_pitch_end_first_in_first_out() {
+        // Two C4s, the second struck before the first is released, on the
+        // notes track of a type 1 file
+        let file = tempo_change(&[
+            (0, &[0x90, 60, 100]),
+            (240, &[0x90, 60, 50]),
+            (240, &[0x80, 60, 0]),
+            (960, &[0x80, 60, 0]),
+        ]);
+        let notes: Vec<(u8, u64, u64, f64, f64)> = file.tracks[1]
+            .notes
+            .iter()
+            .map(|n| (n.velocity, n.start_tick, n.end_tick, n.start, n.end))
+            .collect();
+        assert_eq!(
+            notes,
+            [(100, 0, 480, 0.0, 0.5), (50, 240, 1440, 0.25, 1.25)]
+        );
+        assert!(file.tracks[0].notes.is_empty());
+    }
+
+    #[test]
+    fn key_signatures_are_named() {
+        let keys = track(&[
+            (0, &[0xff, 0x59, 2, 0, 0]),
+            (480, &[0xff, 0x59, 2, 0xfd, 0]),
+            (480, &[0xff, 0x59, 2, 3, 1]),
+            (480, &[0xff, 0x59, 2, 7, 0]),
+            (480, &[0xff, 0x59, 2, 0xf9, 1]),
+        ]);
+        let file = parse(&smf(0, [0x01, 0xe0], &[keys])).unwrap();
+        let names: Vec<&str> = file
+            .key_signatures
+            .iter()
+            .map(|key| key.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            ["C major", "Eb major", "F# minor", "C# major", "Ab minor"]
+        );
+        assert_eq!(file.key_signatures[1].sharps, -3);
+        assert_eq!(file.key_signatures[1].seconds, 0.5);
+    }
+}
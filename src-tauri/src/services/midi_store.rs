@@ -0,0 +1,41 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::midi::IndexedMidi;
+use crate::errors::{AppError, AppResult};
+
+/// Parsed MIDI files kept alive between commands, addressed by a numeric handle.
+#[derive(Default)]
+pub struct MidiStore {
+    next_handle: AtomicU32,
+    entries: Mutex<HashMap<u32, Arc<IndexedMidi>>>,
+}
+
+impl MidiStore {
+    pub fn insert(&self, midi: IndexedMidi) -> u32 {
+        let handle = self.next_handle.fetch_add(1, Ordering::Relaxed) + 1;
+        self.entries
+            .lock()
+            .expect("midi store poisoned")
+            .insert(handle, Arc::new(midi));
+        handle
+    }
+
+    pub fn get(&self, handle: u32) -> AppResult<Arc<IndexedMidi>> {
+        self.entries
+            .lock()
+            .expect("midi store poisoned")
+            .get(&handle)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("Unknown MIDI handle: {}", handle)))
+    }
+
+    pub fn remove(&self, handle: u32) -> bool {
+        self.entries
+            .lock()
+            .expect("midi store poisoned")
+            .remove(&handle)
+            .is_some()
+    }
+}
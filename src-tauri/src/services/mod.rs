@@ -0,0 +1,14 @@
+pub mod analyzer;
+pub mod audio_store;
+pub mod colormap;
+pub mod decoder;
+pub mod encoder;
+pub mod ffmpeg;
+pub mod figure;
+pub mod midi;
+pub mod midi_store;
+pub mod peak_cache;
+pub mod render_store;
+pub mod settings;
+pub mod storage;
+pub mod synth;
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use super::analyzer::peaks::PeakPyramid;
+use super::decoder::DecodedAudio;
+use crate::errors::{AppError, AppResult};
+
+/// A signal of a decoded file (one channel or the mixdown) with its peak pyramid.
+pub struct PeakSource {
+    pub samples: Arc<[f32]>,
+    pub pyramid: PeakPyramid,
+}
+
+/// Audio handle and channel (`None` = mixdown).
+type PeakKey = (u32, Option<usize>);
+
+/// Peak pyramids per audio handle and channel, built on
+/// first use so every later zoom or scroll is cheap. Cloning shares the cache.
+#[derive(Clone, Default)]
+pub struct PeakCache {
+    entries: Arc<Mutex<HashMap<PeakKey, Arc<PeakSource>>>>,
+}
+
+impl PeakCache {
+    pub fn get_or_build(
+        &self,
+        handle: u32,
+        channel: Option<usize>,
+        audio: &DecodedAudio,
+    ) -> AppResult<Arc<PeakSource>> {
+        let key = (handle, channel);
+        if let Some(source) = self.entries.lock().expect("peak cache poisoned").get(&key) {
+            return Ok(source.clone());
+        }
+
+        let samples: Arc<[f32]> = match channel {
+            Some(index) => audio
+                .channels
+                .get(index)
+                .ok_or_else(|| {
+                    AppError::InvalidArgument(format!("Audio has no channel {}", index))
+                })?
+                .as_slice()
+                .into(),
+            None => audio.mixdown().into(),
+        };
+        let source = Arc::new(PeakSource {
+            pyramid: PeakPyramid::build(&samples),
+            samples,
+        });
+        self.entries
+            .lock()
+            .expect("peak cache poisoned")
+            .insert(key, source.clone());
+        Ok(source)
+    }
+
+    /// Drop every pyramid of a released handle.
+    pub fn remove(&self, handle: u32) {
+        self.entries
+            .lock()
+            .expect("peak cache poisoned")
+            .retain(|(h, _), _| *h != handle);
+    }
+}
@@ -0,0 +1,45 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::encoder::RenderJob;
+use crate::errors::{AppError, AppResult};
+
+/// Offline render jobs in progress, addressed by job id.
+///
+/// The store lock only guards the map; each job locks itself, so a frame
+/// being written to one FFmpeg pipe never holds up the other commands.
+#[derive(Default)]
+pub struct RenderStore {
+    next_id: AtomicU32,
+    jobs: Mutex<HashMap<u32, Arc<RenderJob>>>,
+}
+
+impl RenderStore {
+    pub fn insert(&self, job: RenderJob) -> u32 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        self.jobs
+            .lock()
+            .expect("render store poisoned")
+            .insert(id, Arc::new(job));
+        id
+    }
+
+    pub fn get(&self, id: u32) -> AppResult<Arc<RenderJob>> {
+        self.jobs
+            .lock()
+            .expect("render store poisoned")
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| AppError::NotFound(format!("Unknown render job: {}", id)))
+    }
+
+    /// Remove a job. A `push_frame` already writing to it keeps its own reference.
+    pub fn take(&self, id: u32) -> AppResult<Arc<RenderJob>> {
+        self.jobs
+            .lock()
+            .expect("render store poisoned")
+            .remove(&id)
+            .ok_or_else(|| AppError::NotFound(format!("Unknown render job: {}", id)))
+    }
+}
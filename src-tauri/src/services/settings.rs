@@ -0,0 +1,218 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+
+use super::ffmpeg::profile::ProfileSpec;
+use crate::errors::{AppError, AppResult};
+
+const SETTINGS_FILE: &str = "settings.json";
+
+/// App-wide preferences, stored as JSON in the app config dir.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppSettings {
+    /// FFmpeg binary to use before looking for a sidecar or searching `PATH`.
+    pub ffmpeg_path: Option<String>,
+    /// Where export save dialogs open.
+    pub default_export_dir: Option<String>,
+    /// Profile for exports that do not specify one (otherwise `medium`).
+    pub default_export_profile: Option<ProfileSpec>,
+}
+
+pub struct SettingsStore {
+    path: PathBuf,
+    settings: RwLock<AppSettings>,
+}
+
+impl SettingsStore {
+    /// Load `settings.json` from `config_dir`. A missing or unreadable file
+    /// yields the defaults so a bad file never keeps the app from starting.
+    pub fn load(config_dir: &Path) -> Self {
+        let path = config_dir.join(SETTINGS_FILE);
+        let settings = fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        SettingsStore {
+            path,
+            settings: RwLock::new(settings),
+        }
+    }
+
+    pub fn get(&self) -> AppSettings {
+        self.settings
+            .read()
+            .expect("settings store poisoned")
+            .clone()
+    }
+
+    pub fn ffmpeg_path(&self) -> Option<PathBuf> {
+        self.get().ffmpeg_path.map(PathBuf::from)
+    }
+
+    pub fn default_export_profile(&self) -> Option<ProfileSpec> {
+        self.get().default_export_profile
+    }
+
+    /// Validate, persist and apply new settings. Blank paths are cleared, and
+    /// a custom export profile must be valid on its own (its container is only
+    /// known once an export picks a file).
+    pub fn update(&self, settings: AppSettings) -> AppResult<AppSettings> {
+        let settings = AppSettings {
+            ffmpeg_path: non_blank(settings.ffmpeg_path),
+            default_export_dir: non_blank(settings.default_export_dir),
+            ..settings
+        };
+        if let Some(path) = &settings.ffmpeg_path {
+            if !Path::new(path).is_file() {
+                return Err(AppError::InvalidArgument(format!(
+                    "FFmpeg binary not found: {}",
+                    path
+                )));
+            }
+        }
+        if let Some(dir) = &settings.default_export_dir {
+            if !Path::new(dir).is_dir() {
+                return Err(AppError::InvalidArgument(format!(
+                    "Export directory not found: {}",
+                    dir
+                )));
+            }
+        }
+        if let Some(ProfileSpec::Custom(profile)) = &settings.default_export_profile {
+            profile.validate()?;
+        }
+
+        self.write(&settings)?;
+        *self.settings.write().expect("settings store poisoned") = settings.clone();
+        Ok(settings)
+    }
+
+    fn write(&self, settings: &AppSettings) -> AppResult<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| AppError::io(dir, "create", e))?;
+        }
+        let json = serde_json::to_string_pretty(settings)
+            .map_err(|e| AppError::Internal(format!("Failed to serialize settings: {}", e)))?;
+
+        // Same write-then-rename as project files
+        let temp_path = self.path.with_extension("json.tmp");
+        fs::write(&temp_path, json).map_err(|e| AppError::io(&temp_path, "write", e))?;
+        fs::rename(&temp_path, &self.path).map_err(|e| AppError::io(&self.path, "save", e))
+    }
+}
+
+fn non_blank(value: Option<String>) -> Option<String> {
+    value.filter(|v| !v.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::ffmpeg::profile::{ExportProfile, QualityPreset, RateControl};
+
+    /// An empty config dir of its own for each test, removed on drop.
+    struct ConfigDir(PathBuf);
+
+    impl ConfigDir {
+        fn new(name: &str) -> ConfigDir {
+            let dir = std::env::temp_dir().join(format!(
+                "music-visualizer-settings-{}-{}",
+                std::process::id(),
+                name
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            ConfigDir(dir)
+        }
+    }
+
+    impl Drop for ConfigDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn as_json(settings: &AppSettings) -> serde_json::Value {
+        serde_json::to_value(settings).unwrap()
+    }
+
+    #[test]
+    fn update_persists_across_loads() {
+        let config = ConfigDir::new("round-trip");
+        let export_dir = std::env::temp_dir().to_string_lossy().into_owned();
+        let store = SettingsStore::load(&config.0);
+        assert_eq!(as_json(&store.get()), as_json(&AppSettings::default()));
+
+        let saved = store
+            .update(AppSettings {
+                ffmpeg_path: Some("  ".to_string()),
+                default_export_dir: Some(export_dir.clone()),
+                default_export_profile: Some(ProfileSpec::Preset(QualityPreset::High)),
+            })
+            .unwrap();
+        assert_eq!(saved.ffmpeg_path, None);
+        assert_eq!(saved.default_export_dir, Some(export_dir));
+
+        let reloaded = SettingsStore::load(&config.0);
+        assert_eq!(as_json(&reloaded.get()), as_json(&saved));
+        assert!(!config.0.join("settings.json.tmp").exists());
+    }
+
+    #[test]
+    fn corrupt_file_loads_the_defaults() {
+        let config = ConfigDir::new("corrupt");
+        fs::create_dir_all(&config.0).unwrap();
+        fs::write(config.0.join(SETTINGS_FILE), "{ not json").unwrap();
+        let store = SettingsStore::load(&config.0);
+        assert_eq!(as_json(&store.get()), as_json(&AppSettings::default()));
+    }
+
+    #[test]
+    fn update_refuses_bad_paths() {
+        let config = ConfigDir::new("bad-paths");
+        let store = SettingsStore::load(&config.0);
+        let temp_dir = std::env::temp_dir().to_string_lossy().into_owned();
+
+        // A directory is not an FFmpeg binary
+        let ffmpeg_dir = AppSettings {
+            ffmpeg_path: Some(temp_dir),
+            ..AppSettings::default()
+        };
+        assert!(matches!(
+            store.update(ffmpeg_dir),
+            Err(AppError::InvalidArgument(_))
+        ));
+
+        let missing_dir = AppSettings {
+            default_export_dir: Some("/nonexistent/music-visualizer".to_string()),
+            ..AppSettings::default()
+        };
+        assert!(matches!(
+            store.update(missing_dir),
+            Err(AppError::InvalidArgument(_))
+        ));
+        assert!(!config.0.join(SETTINGS_FILE).exists());
+    }
+
+    #[test]
+    fn update_refuses_an_invalid_export_profile() {
+        let config = ConfigDir::new("bad-profile");
+        let store = SettingsStore::load(&config.0);
+        let two_pass_crf = ExportProfile {
+            rate_control: RateControl::Crf { value: 23 },
+            two_pass: true,
+            ..QualityPreset::Medium.profile(Path::new("out.mp4"))
+        };
+        let settings = AppSettings {
+            default_export_profile: Some(ProfileSpec::Custom(two_pass_crf)),
+            ..AppSettings::default()
+        };
+        assert!(matches!(
+            store.update(settings),
+            Err(AppError::InvalidArgument(_))
+        ));
+        assert!(store.default_export_profile().is_none());
+    }
+}
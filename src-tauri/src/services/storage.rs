@@ -0,0 +1,30 @@
+use std::fs;
+use std::path::Path;
+
+use crate::domain::migration;
+use crate::domain::project::{ProjectState, PROJECT_VERSION};
+use crate::errors::{AppError, AppResult};
+
+pub fn write_project(path: &Path, project: &ProjectState) -> AppResult<()> {
+    let project = ProjectState {
+        version: PROJECT_VERSION,
+        ..project.clone()
+    };
+    let json = serde_json::to_string_pretty(&project)
+        .map_err(|e| AppError::Internal(format!("Failed to serialize project: {}", e)))?;
+
+    // Write next to the target and rename so a crash never leaves a truncated project
+    let temp_path = path.with_extension("mvproj.tmp");
+    fs::write(&temp_path, json).map_err(|e| AppError::io(&temp_path, "write", e))?;
+    fs::rename(&temp_path, path).map_err(|e| AppError::io(path, "save", e))
+}
+
+pub fn read_project(path: &Path) -> AppResult<ProjectState> {
+    let json = fs::read_to_string(path).map_err(|e| AppError::io(path, "read", e))?;
+    let mut document: serde_json::Value =
+        serde_json::from_str(&json).map_err(|e| AppError::InvalidProject(e.to_string()))?;
+
+    migration::migrate(&mut document)?;
+
+    serde_json::from_value(document).map_err(|e| AppError::InvalidProject(e.to_string()))
+}
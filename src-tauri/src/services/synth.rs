@@ -0,0 +1,255 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use rustysynth::{SoundFont, Synthesizer, SynthesizerSettings};
+use serde::Deserialize;
+
+use super::decoder::DecodedAudio;
+use super::midi::MidiFile;
+use crate::errors::{AppError, AppResult};
+
+const NOTE_OFF: i32 = 0x80;
+const NOTE_ON: i32 = 0x90;
+const CONTROL_CHANGE: i32 = 0xB0;
+const PROGRAM_CHANGE: i32 = 0xC0;
+
+/// Longest rendering, tail included. The whole song is held in memory as two
+/// f32 channels (about 1.4 GB for an hour at 48 kHz).
+const MAX_DURATION: f64 = 3600.0;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SynthOptions {
+    pub sample_rate: u32,
+    /// Seconds rendered after the last event so releases and reverb ring out.
+    pub tail: f64,
+    /// Master volume of the synthesizer (0.5 is its own default).
+    pub gain: f32,
+    pub reverb_and_chorus: bool,
+}
+
+impl Default for SynthOptions {
+    fn default() -> Self {
+        SynthOptions {
+            sample_rate: 44_100,
+            tail: 2.0,
+            gain: 0.5,
+            reverb_and_chorus: true,
+        }
+    }
+}
+
+/// One MIDI message at `tick`. Messages at the same tick are sent controllers
+/// and programs first, then note-offs, then note-ons, so that a sustain pedal
+/// or instrument change applies to the notes that start with it and a
+/// repeated note is released before it is struck again. The note-off of a
+/// zero-length note (common for drum hits) goes last, after its own note-on.
+struct Event {
+    tick: u64,
+    order: u8,
+    seconds: f64,
+    message: [i32; 4],
+}
+
+fn events(midi: &MidiFile) -> Vec<Event> {
+    let mut events = Vec::new();
+    for track in &midi.tracks {
+        for change in &track.control_changes {
+            events.push(Event {
+                tick: change.tick,
+                order: 0,
+                seconds: change.seconds,
+                message: [
+                    change.channel as i32,
+                    CONTROL_CHANGE,
+                    change.controller as i32,
+                    change.value as i32,
+                ],
+            });
+        }
+        for change in &track.program_changes {
+            events.push(Event {
+                tick: change.tick,
+                order: 0,
+                seconds: change.seconds,
+                message: [
+                    change.channel as i32,
+                    PROGRAM_CHANGE,
+                    change.program as i32,
+                    0,
+                ],
+            });
+        }
+        for note in &track.notes {
+            events.push(Event {
+                tick: note.end_tick,
+                order: if note.end_tick == note.start_tick {
+                    3
+                } else {
+                    1
+                },
+                seconds: note.end,
+                message: [note.channel as i32, NOTE_OFF, note.pitch as i32, 0],
+            });
+            events.push(Event {
+                tick: note.start_tick,
+                order: 2,
+                seconds: note.start,
+                message: [
+                    note.channel as i32,
+                    NOTE_ON,
+                    note.pitch as i32,
+                    note.velocity as i32,
+                ],
+            });
+        }
+    }
+    // Stable, so events keep track order within a tick
+    events.sort_by_key(|event| (event.tick, event.order));
+    events
+}
+
+/// Render `midi` to stereo PCM with the instruments of an SF2 SoundFont.
+/// Program changes (and bank selects), velocities and every controller,
+/// including the sustain pedal, are sent to the synthesizer as they occur.
+pub fn render(
+    midi: &MidiFile,
+    soundfont: &Path,
+    output: PathBuf,
+    options: &SynthOptions,
+) -> AppResult<DecodedAudio> {
+    if !(8_000..=192_000).contains(&options.sample_rate) {
+        return Err(AppError::InvalidArgument(format!(
+            "sampleRate must be between 8000 and 192000, got {}",
+            options.sample_rate
+        )));
+    }
+    if !options.tail.is_finite() || !(0.0..=60.0).contains(&options.tail) {
+        return Err(AppError::InvalidArgument(
+            "tail must be between 0 and 60 seconds".to_string(),
+        ));
+    }
+    if !options.gain.is_finite() || options.gain <= 0.0 {
+        return Err(AppError::InvalidArgument(
+            "gain must be positive".to_string(),
+        ));
+    }
+    let duration = midi.duration + options.tail;
+    if !duration.is_finite() || duration > MAX_DURATION {
+        return Err(AppError::InvalidArgument(format!(
+            "The MIDI file is {:.0} minutes long; at most {:.0} minutes can be synthesized",
+            midi.duration / 60.0,
+            MAX_DURATION / 60.0
+        )));
+    }
+
+    let file = File::open(soundfont).map_err(|e| AppError::io(soundfont, "open", e))?;
+    let font = SoundFont::new(&mut BufReader::new(file)).map_err(|e| {
+        AppError::UnsupportedFormat(format!(
+            "{}: invalid SoundFont ({})",
+            soundfont.display(),
+            e
+        ))
+    })?;
+    let mut settings = SynthesizerSettings::new(options.sample_rate as i32);
+    settings.enable_reverb_and_chorus = options.reverb_and_chorus;
+    let mut synthesizer = Synthesizer::new(&Arc::new(font), &settings)
+        .map_err(|e| AppError::Internal(format!("Could not start the synthesizer: {}", e)))?;
+    synthesizer.set_master_volume(options.gain);
+
+    let sample_rate = options.sample_rate as f64;
+    let total = (duration * sample_rate).ceil() as usize;
+    let mut left = vec![0.0f32; total];
+    let mut right = vec![0.0f32; total];
+    let mut cursor = 0;
+    for event in events(midi) {
+        let at = ((event.seconds * sample_rate).round() as usize).min(total);
+        if at > cursor {
+            synthesizer.render(&mut left[cursor..at], &mut right[cursor..at]);
+            cursor = at;
+        }
+        let [channel, command, data1, data2] = event.message;
+        synthesizer.process_midi_message(channel, command, data1, data2);
+    }
+    synthesizer.render(&mut left[cursor..], &mut right[cursor..]);
+
+    Ok(DecodedAudio {
+        path: output,
+        sample_rate: options.sample_rate,
+        channels: vec![left, right],
+        temporary: false,
+    })
+}
+
+/// Write PCM to `audio.path` as a 32-bit float WAV file.
+pub fn write_wav(audio: &DecodedAudio) -> AppResult<()> {
+    let path = audio.path.as_path();
+    let spec = hound::WavSpec {
+        channels: audio.channels.len() as u16,
+        sample_rate: audio.sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let wav_error = |e: hound::Error| match e {
+        hound::Error::IoError(e) => AppError::io(path, "write", e),
+        other => AppError::Internal(format!("Failed to write {}: {}", path.display(), other)),
+    };
+    let mut writer = hound::WavWriter::create(path, spec).map_err(wav_error)?;
+    for frame in 0..audio.frames() {
+        for channel in &audio.channels {
+            writer.write_sample(channel[frame]).map_err(wav_error)?;
+        }
+    }
+    writer.finalize().map_err(wav_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::midi;
+
+    /// Type 0 file at 96 ticks per quarter: a zero-length note 36 at tick 0,
+    /// then note 60 released and struck again at tick 96.
+    const SMF: &[u8] = b"MThd\0\0\0\x06\0\0\0\x01\0\x60\
+        MTrk\0\0\0\x18\
+        \0\x99\x24\x64\0\x89\x24\0\
+        \0\x90\x3c\x50\x60\x80\x3c\0\0\x90\x3c\x50\
+        \x60\x80\x3c\0\0\xff\x2f\0";
+
+    #[test]
+    fn same_tick_events_release_before_striking_except_zero_length_notes() {
+        let midi = midi::parse(SMF).unwrap();
+        let messages: Vec<(u64, i32, i32)> = events(&midi)
+            .iter()
+            .filter(|event| matches!(event.message[1], NOTE_ON | NOTE_OFF))
+            .map(|event| (event.tick, event.message[1], event.message[2]))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (0, NOTE_ON, 0x24),
+                (0, NOTE_ON, 0x3c),
+                (0, NOTE_OFF, 0x24),
+                (96, NOTE_OFF, 0x3c),
+                (96, NOTE_ON, 0x3c),
+                (192, NOTE_OFF, 0x3c),
+            ]
+        );
+    }
+
+    #[test]
+    fn songs_over_an_hour_are_refused_before_rendering() {
+        let mut midi = midi::parse(SMF).unwrap();
+        midi.duration = MAX_DURATION;
+        // Checked before the SoundFont is opened or any sample allocated
+        let result = render(
+            &midi,
+            Path::new("/nonexistent/font.sf2"),
+            PathBuf::from("/nonexistent/out.wav"),
+            &SynthOptions::default(),
+        );
+        assert!(matches!(result, Err(AppError::InvalidArgument(_))));
+    }
+}
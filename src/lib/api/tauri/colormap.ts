@@ -0,0 +1,82 @@
+import { invoke } from '@tauri-apps/api/core';
+
+/** 組み込みのカラーマップ（Rust 側 Colormap と対応） */
+export type ColormapName =
+  | 'viridis'
+  | 'magma'
+  | 'inferno'
+  | 'plasma'
+  | 'cividis'
+  | 'turbo'
+  | 'grayscale'
+  | 'hot'
+  | 'cool';
+
+/** ユーザー定義グラデーションの色（position は 0〜1、color は #rrggbb） */
+export type GradientStop = { position: number; color: string };
+
+/** カラーマップの指定（Rust 側 ColormapSpec と対応）。名前か stops を持つオブジェクト */
+export type ColormapSpec = ColormapName | { stops: GradientStop[] };
+
+export const COLORMAP_OPTIONS: { value: ColormapName; label: string }[] = [
+  { value: 'viridis', label: 'Viridis' },
+  { value: 'magma', label: 'Magma' },
+  { value: 'inferno', label: 'Inferno' },
+  { value: 'plasma', label: 'Plasma' },
+  { value: 'cividis', label: 'Cividis' },
+  { value: 'turbo', label: 'Turbo' },
+  { value: 'grayscale', label: 'Grayscale' },
+  { value: 'hot', label: 'Hot' },
+  { value: 'cool', label: 'Cool' }
+];
+
+// 取得済みの LUT。key は ColormapSpec の JSON
+const luts = new Map<string, Uint8Array>();
+const pending = new Map<string, Promise<Uint8Array>>();
+
+/** 強度 0〜255 に対応する RGB の LUT（256 * 3 バイト）を Rust 側から取得する */
+export function loadColormapLut(spec: ColormapSpec): Promise<Uint8Array> {
+  const key = JSON.stringify(spec);
+  const cached = luts.get(key);
+  if (cached) return Promise.resolve(cached);
+  let request = pending.get(key);
+  if (!request) {
+    request = invoke<ArrayBuffer>('get_colormap_lut', { colormap: spec })
+      .then((buffer) => {
+        const lut = new Uint8Array(buffer);
+        luts.set(key, lut);
+        return lut;
+      })
+      .finally(() => pending.delete(key));
+    pending.set(key, request);
+  }
+  return request;
+}
+
+/** 描画ループ用: 取得済みなら LUT を返し、未取得なら取得を開始して null を返す */
+export function peekColormapLut(spec: ColormapSpec): Uint8Array | null {
+  const lut = luts.get(JSON.stringify(spec));
+  if (lut) return lut;
+  loadColormapLut(spec).catch((error) => console.error('Failed to load colormap:', error));
+  return null;
+}
+
+/** LUT の強度 intensity の色。LUT が未取得の間はグレースケール */
+export function lutColor(lut: Uint8Array | null, intensity: number): [number, number, number] {
+  if (!lut) return [intensity, intensity, intensity];
+  const offset = Math.max(0, Math.min(255, Math.round(intensity))) * 3;
+  return [lut[offset], lut[offset + 1], lut[offset + 2]];
+}
+
+/** 凡例表示用の CSS linear-gradient */
+export function lutGradientCss(lut: Uint8Array | null): string {
+  if (!lut) return 'linear-gradient(to right, #000000, #ffffff)';
+  const stops: string[] = [];
+  for (let i = 0; i < 256; i += 15) {
+    const [r, g, b] = lutColor(lut, i);
+    stops.push(`rgb(${r}, ${g}, ${b}) ${((i / 255) * 100).toFixed(1)}%`);
+  }
+  const [r, g, b] = lutColor(lut, 255);
+  stops.push(`rgb(${r}, ${g}, ${b}) 100%`);
+  return `linear-gradient(to right, ${stops.join(', ')})`;
+}
@@ -0,0 +1,130 @@
+import { invoke } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
+
+/** conversion-progress イベントの中身（Rust 側 ConversionProgress と対応） */
+export type ConversionProgress = {
+  jobId: string;
+  /** measuring: ラウドネス正規化のための音声の計測、encoding: 書き出し */
+  phase: 'measuring' | 'encoding';
+  /** 入力の長さが不明な場合（Duration ヘッダのない WebM など）は null */
+  percent: number | null;
+  fps: number | null;
+  etaSeconds: number | null;
+  outTimeSeconds: number;
+  done: boolean;
+};
+
+/** エンコード設定（Rust 側 ExportProfile と対応） */
+export type ExportProfile = {
+  videoCodec: 'x264' | 'x265' | 'vp9' | 'av1' | 'prores';
+  rateControl: { mode: 'crf'; value: number } | { mode: 'bitrate'; kbps: number };
+  preset?: string;
+  pixelFormat?: string;
+  proresProfile?: 'proxy' | 'lt' | 'standard' | 'hq' | '4444' | '4444xq';
+  audioCodec: 'aac' | 'opus' | 'pcm' | 'flac' | 'none';
+  audioBitrateKbps?: number;
+  twoPass?: boolean;
+};
+
+/**
+ * 音声のラウドネス正規化（Rust 側 LoudnessTarget と対応）。省略した項目は既定値。
+ * gain は Rust で計測して一定のゲインをかける（トゥルーピークが上限を超えない範囲）、
+ * loudnorm は FFmpeg の 2 パス loudnorm
+ */
+export type LoudnessTarget = {
+  /** 統合ラウドネス（LUFS、既定 -14） */
+  integrated?: number;
+  /** トゥルーピークの上限（dBTP、既定 -1） */
+  truePeak?: number;
+  /** ラウドネスレンジ（LU、既定 11。loudnorm のみ） */
+  loudnessRange?: number;
+  method?: 'gain' | 'loudnorm';
+};
+
+/** globalSettings.quality の値。master は ProRes 4444 XQ + PCM */
+export type QualityPreset = 'low' | 'medium' | 'high' | 'ultra' | 'master';
+
+/** 出力先が既に存在する場合の扱い（Rust 側 ExistingFilePolicy と対応）。unique は "name (1).mp4" のように別名にする */
+export type ExistingFilePolicy = 'overwrite' | 'unique' | 'fail';
+
+export type ConvertVideoArgs = {
+  inputPath: string;
+  outputFormat: string;
+  /** 省略時は入力ファイルの拡張子を outputFormat に置き換えたパス */
+  outputPath?: string;
+  /** 省略時は unique。入力ファイル自体は上書きしない */
+  onExisting?: ExistingFilePolicy;
+  /** 省略時は medium */
+  profile?: QualityPreset | ExportProfile;
+  /** 指定すると音声をこのラウドネスに正規化する */
+  loudness?: LoudnessTarget;
+};
+
+/** check_ffmpeg_installed の結果（Rust 側 FfmpegReport と対応） */
+export type FfmpegReport = {
+  installed: boolean;
+  path: string | null;
+  /** configured: 設定で指定、sidecar: アプリに同梱、path: PATH から検出 */
+  source: 'configured' | 'sidecar' | 'path' | null;
+  version: string | null;
+  encoders: string[];
+  muxers: string[];
+  hwaccels: string[];
+  /** このビルドで出力できる形式（拡張子） */
+  formats: string[];
+};
+
+export function checkFfmpeg(): Promise<FfmpegReport> {
+  return invoke<FfmpegReport>('check_ffmpeg_installed');
+}
+
+export function createConversionJobId(): string {
+  return crypto.randomUUID();
+}
+
+/** jobId の conversion-progress イベントを onProgress に流す。戻り値で購読をやめる */
+export function listenConversionProgress(
+  jobId: string,
+  onProgress: (progress: ConversionProgress) => void
+): Promise<UnlistenFn> {
+  return listen<ConversionProgress>('conversion-progress', (event) => {
+    if (event.payload.jobId === jobId) {
+      onProgress(event.payload);
+    }
+  });
+}
+
+/** convert_video を呼び出し、完了まで進捗を onProgress に流す。jobId で cancelConversion できる。戻り値は実際の出力パス */
+export async function runConversion(
+  jobId: string,
+  args: ConvertVideoArgs,
+  onProgress: (progress: ConversionProgress) => void
+): Promise<string> {
+  const unlisten = await listenConversionProgress(jobId, onProgress);
+  try {
+    return await invoke<string>('convert_video', { ...args, jobId });
+  } finally {
+    unlisten();
+  }
+}
+
+export function cancelConversion(jobId: string): Promise<boolean> {
+  return invoke<boolean>('cancel_conversion', { jobId });
+}
+
+function formatSeconds(seconds: number): string {
+  const total = Math.max(0, Math.round(seconds));
+  return `${Math.floor(total / 60)}:${String(total % 60).padStart(2, '0')}`;
+}
+
+/** 例: "42% · 58 fps · ETA 0:31"、計測中は先頭に "Measuring loudness"（不明な値は省略） */
+export function formatConversionProgress(progress: ConversionProgress | null): string {
+  if (!progress) return 'Starting...';
+  const parts = [
+    progress.percent !== null ? `${Math.round(progress.percent)}%` : formatSeconds(progress.outTimeSeconds),
+    progress.fps !== null ? `${Math.round(progress.fps)} fps` : null,
+    progress.etaSeconds !== null ? `ETA ${formatSeconds(progress.etaSeconds)}` : null
+  ];
+  if (progress.phase === 'measuring') parts.unshift('Measuring loudness');
+  return parts.filter(Boolean).join(' · ');
+}
@@ -0,0 +1,54 @@
+/** Rust 側 AppError のシリアライズ形式（errors.rs と対応） */
+export type AppErrorKind =
+  | 'FfmpegMissing'
+  | 'FfmpegFailed'
+  | 'Io'
+  | 'Cancelled'
+  | 'UnsupportedFormat'
+  | 'IncompatibleCodec'
+  | 'Decode'
+  | 'InvalidProject'
+  | 'InvalidArgument'
+  | 'NotFound'
+  | 'Internal';
+
+export type AppError = {
+  kind: AppErrorKind;
+  message: string;
+  exitCode?: number | null;
+  stderrTail?: string;
+  path?: string | null;
+  /** IncompatibleCodec のみ: 出力形式、指定されたコーデック、その形式で使えるコーデック */
+  container?: string;
+  codec?: string;
+  supported?: string[];
+};
+
+export function isAppError(error: unknown): error is AppError {
+  return typeof error === 'object' && error !== null && 'kind' in error && 'message' in error;
+}
+
+/** ユーザー操作で中断された場合は true（エラーとして通知しない） */
+export function isCancelled(error: unknown): boolean {
+  return isAppError(error) && error.kind === 'Cancelled';
+}
+
+/** invoke() の例外をユーザー向けの文言に変換する（kind ごとに対処法を添える） */
+export function describeError(error: unknown): string {
+  if (!isAppError(error)) {
+    return String(error);
+  }
+  switch (error.kind) {
+    case 'FfmpegMissing':
+      return 'FFmpeg is not installed.\n\nInstall FFmpeg (see FFMPEG_SETUP.md) and make sure it is on your PATH, then try again.';
+    case 'FfmpegFailed':
+      return `FFmpeg exited with code ${error.exitCode ?? 'unknown'}.\n\n${error.stderrTail ?? ''}`;
+    case 'IncompatibleCodec':
+      return `${error.message}\n\nChoose a different output format or codec.`;
+    case 'UnsupportedFormat':
+    case 'Decode':
+      return `${error.message}\n\nPlease pick a different file or format.`;
+    default:
+      return error.message;
+  }
+}
@@ -0,0 +1,157 @@
+import { invoke } from '@tauri-apps/api/core';
+
+/** ノート（Rust 側 MidiNote と対応）。start / end はテンポ変化を反映した秒 */
+export type MidiNote = {
+  pitch: number;
+  /** 0〜127 */
+  velocity: number;
+  channel: number;
+  startTick: number;
+  endTick: number;
+  start: number;
+  end: number;
+};
+
+export type ProgramChange = { tick: number; seconds: number; channel: number; program: number };
+
+export type ControlChange = { tick: number; seconds: number; channel: number; controller: number; value: number };
+
+export type MidiTrack = {
+  index: number;
+  name: string | null;
+  /** 開始 tick 順 */
+  notes: MidiNote[];
+  programChanges: ProgramChange[];
+  controlChanges: ControlChange[];
+  endTick: number;
+};
+
+export type TempoChange = { tick: number; seconds: number; microsecondsPerQuarter: number; bpm: number };
+
+export type TimeSignature = { tick: number; seconds: number; numerator: number; denominator: number };
+
+export type KeySignature = {
+  tick: number;
+  seconds: number;
+  /** 正はシャープ、負はフラットの数 */
+  sharps: number;
+  minor: boolean;
+  /** 例: "Eb major" */
+  name: string;
+};
+
+/** 小節（Rust 側 MidiBar と対応）。拍子が小節の途中で変わると、その小節は短くなる */
+export type MidiBar = {
+  /** 1 始まり */
+  number: number;
+  startTick: number;
+  endTick: number;
+  start: number;
+  end: number;
+  numerator: number;
+  denominator: number;
+  /** この小節の 1 拍（denominator 分音符）の tick 数 */
+  beatTicks: number;
+};
+
+/** parse_midi の結果（Rust 側 MidiFile と対応） */
+export type MidiFile = {
+  format: number;
+  /** SMPTE タイムコードのファイルでは null */
+  ticksPerQuarter: number | null;
+  durationTicks: number;
+  duration: number;
+  tracks: MidiTrack[];
+  /** tick 0 から始まるテンポマップ（未指定なら 120 BPM） */
+  tempos: TempoChange[];
+  /** tick 0 から始まる拍子の変化（未指定なら 4/4） */
+  timeSignatures: TimeSignature[];
+  keySignatures: KeySignature[];
+  /** テンポ・拍子マップに沿った全小節（durationTicks まで） */
+  bars: MidiBar[];
+};
+
+/** SMF（type 0/1）を Rust 側で解析する */
+export function parseMidi(path: string): Promise<MidiFile> {
+  return invoke<MidiFile>('parse_midi', { path });
+}
+
+/** load_midi の結果（Rust 側 LoadedMidiInfo と対応） */
+export type LoadedMidiInfo = {
+  handle: number;
+  duration: number;
+  tracks: number;
+  notes: number;
+  /** ノートがないファイルでは null */
+  lowestPitch: number | null;
+  highestPitch: number | null;
+};
+
+/** 時間窓内のノート（Rust 側 WindowNote と対応）。track は tracks の添字 */
+export type WindowNote = MidiNote & { track: number };
+
+/** midi_notes_in_window の結果（Rust 側 MidiWindow と対応） */
+export type MidiWindow = {
+  start: number;
+  end: number;
+  /** 開始時刻順 */
+  notes: WindowNote[];
+  /** 窓と重なる小節 */
+  bars: MidiBar[];
+};
+
+/** MIDI を Rust 側で解析して handle で保持する（release_midi で解放） */
+export function loadMidi(path: string): Promise<LoadedMidiInfo> {
+  return invoke<LoadedMidiInfo>('load_midi', { path });
+}
+
+/** start〜end 秒に鳴っているノートと、その区間の小節を返す */
+export function midiNotesInWindow(handle: number, start: number, end: number): Promise<MidiWindow> {
+  return invoke<MidiWindow>('midi_notes_in_window', { handle, start, end });
+}
+
+export function releaseMidi(handle: number): Promise<boolean> {
+  return invoke<boolean>('release_midi', { handle });
+}
+
+/** synthesize_midi のオプション（Rust 側 SynthOptions と対応）。省略した項目は既定値 */
+export type SynthOptions = {
+  /** 既定 44100 */
+  sampleRate?: number;
+  /** 最後のイベント後に鳴らし続ける秒数（リリース・リバーブ用、既定 2） */
+  tail?: number;
+  /** マスター音量（既定 0.5） */
+  gain?: number;
+  reverbAndChorus?: boolean;
+};
+
+/** synthesize_midi の結果（Rust 側 SynthesizedMidiInfo と対応）。handle は decode_audio と同じ音声 handle */
+export type SynthesizedMidiInfo = {
+  /** 書き出した WAV のパス */
+  path: string;
+  handle: number;
+  sampleRate: number;
+  channels: number;
+  frames: number;
+  duration: number;
+};
+
+/**
+ * MIDI を SF2 SoundFont で PCM に合成し、WAV に書き出す（outputPath 省略時は一時ファイル）。
+ * プログラムチェンジ・ベロシティ・サステインペダルを反映する。release_decoded_audio で解放
+ */
+export function synthesizeMidi(
+  path: string,
+  soundFont: string,
+  outputPath: string | null = null,
+  options: SynthOptions = {}
+): Promise<SynthesizedMidiInfo> {
+  return invoke<SynthesizedMidiInfo>('synthesize_midi', { path, soundFont, outputPath, options });
+}
+
+/** MIDI ノート番号の音名（60 → "C4"） */
+export function midiNoteName(note: number): string {
+  const names = ['C', 'C#', 'D', 'D#', 'E', 'F', 'F#', 'G', 'G#', 'A', 'A#', 'B'];
+  const octave = Math.floor(note / 12) - 1;
+  return `${names[note % 12]}${octave}`;
+}
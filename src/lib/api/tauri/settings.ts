@@ -0,0 +1,33 @@
+import { invoke } from '@tauri-apps/api/core';
+import { join } from '@tauri-apps/api/path';
+import type { ExportProfile, QualityPreset } from './conversion';
+
+/** アプリ設定（Rust 側 AppSettings と対応。アプリの設定ディレクトリの settings.json に保存） */
+export type AppSettings = {
+  /** 未設定の場合は同梱の ffmpeg、次に PATH を探す */
+  ffmpegPath: string | null;
+  /** 書き出し時の保存ダイアログの初期フォルダ */
+  defaultExportDir: string | null;
+  /** プロファイル未指定の書き出しで使う設定（未設定なら medium） */
+  defaultExportProfile: QualityPreset | ExportProfile | null;
+};
+
+export function getSettings(): Promise<AppSettings> {
+  return invoke<AppSettings>('get_settings');
+}
+
+/** 保存後の設定を返す（空のパスは null になる） */
+export function updateSettings(settings: AppSettings): Promise<AppSettings> {
+  return invoke<AppSettings>('update_settings', { settings });
+}
+
+/** 保存ダイアログの defaultPath。defaultExportDir が設定されていればその中のパスにする */
+export async function exportDefaultPath(fileName: string): Promise<string> {
+  try {
+    const { defaultExportDir } = await getSettings();
+    return defaultExportDir ? await join(defaultExportDir, fileName) : fileName;
+  } catch (error) {
+    console.error('Failed to read settings:', error);
+    return fileName;
+  }
+}